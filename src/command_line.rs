@@ -0,0 +1,230 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// every command and alias understood by `State::execute_command`, for completion
+pub const COMMANDS: &[&str] = &[
+    "b", "binary", "b2",
+    "x", "hex", "hexadecimal", "b16",
+    "d", "decimal", "b10",
+    "pct", "percent", "delta", "pctchange", "markup", "discount",
+    "q", "quit", "exit", "wq",
+    "w", "write", "r", "read", "p", "path",
+    "c", "clear",
+    "s", "st", "store", "l", "ld", "load",
+    "ls", "list", "show", "rm", "del", "mv", "rename", "mv!", "rename!",
+    "def", "define", "undef", "fn", "call", "fns", "functions",
+    "get", "set", "set!",
+    "ws", "workspace", "export", "import", "y", "yank",
+];
+
+// commands whose arguments are variable or function names
+const NAME_COMMANDS: &[&str] = &["s", "st", "store", "l", "ld", "load", "ls", "list", "show", "rm", "del", "mv", "rename", "mv!", "rename!", "undef", "fn", "call"];
+
+// commands whose first argument is the side of the equation, like `l` in `s l rate`, with names after it
+const SIDE_COMMANDS: &[&str] = &["s", "st", "store", "l", "ld", "load"];
+
+// commands whose argument is a file path
+const PATH_COMMANDS: &[&str] = &["w", "write", "r", "read", "p", "path"];
+
+// previously executed commands, navigated with up/down while typing a command
+pub struct History {
+    pub entries: Vec<String>,
+    position: Option<usize>,// index into entries while navigating
+    draft: String,// what was typed before navigating, restored after the newest entry
+    path: PathBuf,
+    max_len: usize,
+    pub persist: bool,// pushes are written to the history file, off for a workspace's history which is saved with it
+}
+
+impl History {
+    // the newest `max_len` entries of the history file
+    pub fn load(path: PathBuf, max_len: usize) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|data| data.lines().map(|line| line.to_owned()).collect())
+            .unwrap_or_default();
+        let mut history = Self {entries, persist: true, ..Self::new(path, max_len)};
+        history.set_max_len(max_len);
+        history
+    }
+
+    // an empty history that isn't written until `persist` is set
+    pub fn new(path: PathBuf, max_len: usize) -> Self {
+        Self {entries: vec![], position: None, draft: "".to_owned(), path, max_len, persist: false}
+    }
+
+    pub fn push(&mut self, command: &str) {
+        self.position = None;
+        let command = command.trim();
+        if command.is_empty() || self.entries.last().is_some_and(|last| last == command) {return};
+        self.entries.push(command.to_owned());
+        if self.entries.len() > self.max_len {
+            self.entries.drain(..self.entries.len() - self.max_len);
+        }
+        if self.persist {
+            _ = fs::write(&self.path, self.entries.join("\n"));
+        }
+    }
+
+    // the entry before the current one, `current` is kept to come back to
+    pub fn older(&mut self, current: &str) -> Option<String> {
+        let position = match self.position {
+            None => {
+                self.draft = current.to_owned();
+                self.entries.len().checked_sub(1)?
+            },
+            Some(position) => position.checked_sub(1)?,
+        };
+        self.position = Some(position);
+        Some(self.entries[position].clone())
+    }
+
+    pub fn newer(&mut self) -> Option<String> {
+        let position = self.position? + 1;
+        if position >= self.entries.len() {
+            self.position = None;
+            return Some(self.draft.clone());
+        }
+        self.position = Some(position);
+        Some(self.entries[position].clone())
+    }
+
+    // swaps in the entries of another workspace
+    pub fn replace(&mut self, entries: Vec<String>) {
+        self.entries = entries;
+        self.position = None;
+    }
+
+    // keeps the newest `max_len` entries, written to the history file on the next push
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+        if self.entries.len() > max_len {
+            self.entries.drain(..self.entries.len() - max_len);
+            self.position = None;
+        }
+    }
+
+    // stop navigating, e.g. when leaving command entry
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+}
+
+// the result of completing a command line
+#[derive(Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub line: String,
+    pub candidates: Vec<String>,// shown when the completion is ambiguous
+}
+
+// completes the last word of `line` from `commands`, `names` or the file system depending on the command
+pub fn complete(line: &str, commands: &[&str], names: &[&str]) -> Completion {
+    let words: Vec<&str> = line.split(' ').collect();
+    if words.len() == 1 {
+        return complete_word(line, "", commands.iter().map(|c| c.to_string()).collect());
+    }
+    let command = words[0];
+    if PATH_COMMANDS.contains(&command) {
+        let (head, path) = line.split_once(' ').unwrap();
+        return complete_word(path, format!("{head} ").as_str(), path_candidates(path));
+    }
+    if SIDE_COMMANDS.contains(&command) && words.len() == 2 {
+        return Completion {line: line.to_owned(), candidates: vec![]};
+    }
+    if NAME_COMMANDS.contains(&command) {
+        let (head, word) = line.rsplit_once(' ').unwrap();
+        return complete_word(word, format!("{head} ").as_str(), names.iter().map(|n| n.to_string()).collect());
+    }
+    Completion {line: line.to_owned(), candidates: vec![]}
+}
+
+fn complete_word(word: &str, head: &str, candidates: Vec<String>) -> Completion {
+    let mut candidates: Vec<String> = candidates.into_iter().filter(|c| c.starts_with(word)).collect();
+    candidates.sort();
+    candidates.dedup();
+    let completed = match candidates.as_slice() {
+        [] => word.to_owned(),
+        [only] => only.clone(),
+        [first, rest @ ..] => {
+            // longest common prefix
+            let mut prefix = first.clone();
+            for candidate in rest {
+                while !candidate.starts_with(prefix.as_str()) {
+                    prefix.pop();
+                }
+            }
+            prefix
+        },
+    };
+    let candidates = if candidates.len() > 1 {candidates} else {vec![]};
+    Completion {line: format!("{head}{completed}"), candidates}
+}
+
+// paths in the directory part of `path` that could complete it, directories end in `/`
+fn path_candidates(path: &str) -> Vec<String> {
+    let (dir, prefix) = match path.rfind('/') {
+        Some(i) => (&path[..=i], &path[i + 1..]),
+        None => ("", path),
+    };
+    let read_dir = if dir.is_empty() {Path::new(".")} else {Path::new(dir)};
+    let entries = match fs::read_dir(read_dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries.filter_map(|entry| {
+        let entry = entry.ok()?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(prefix) {return None};
+        let slash = if entry.path().is_dir() {"/"} else {""};
+        Some(format!("{dir}{name}{slash}"))
+    }).collect()
+}
+
+#[test]
+fn complete_test() {
+    let names = ["rate", "ratio", "total"];
+    assert_eq!(complete("hexa", COMMANDS, &names).line, "hexadecimal");
+    let completion = complete("de", COMMANDS, &names);
+    assert_eq!(completion.line, "de");
+    assert_eq!(completion.candidates, vec!["decimal", "def", "define", "del", "delta"]);
+    let completion = complete("l l ra", COMMANDS, &names);
+    assert_eq!(completion.line, "l l rat");
+    assert_eq!(completion.candidates, vec!["rate", "ratio"]);
+    assert_eq!(complete("show to", COMMANDS, &names).line, "show total");
+    // the side of `s` and `l` isn't a name
+    assert_eq!(complete("s r", COMMANDS, &names), Completion {line: "s r".to_owned(), candidates: vec![]});
+    assert_eq!(complete("s r to", COMMANDS, &names).line, "s r total");
+
+    let dir = std::env::temp_dir().join("minicalc-complete-test");
+    _ = fs::create_dir_all(dir.join("math"));
+    _ = fs::write(dir.join("main.rs"), "");
+    let dir = dir.to_string_lossy();
+    assert_eq!(complete(&format!("r {dir}/mat"), COMMANDS, &names).line, format!("r {dir}/math/"));
+    assert_eq!(complete(&format!("r {dir}/ma"), COMMANDS, &names).candidates, vec![format!("{dir}/main.rs"), format!("{dir}/math/")]);
+    _ = fs::remove_dir_all(dir.as_ref());
+}
+
+#[test]
+fn history_test() {
+    let path = std::env::temp_dir().join("minicalc-history-test");
+    let mut history = History {entries: vec![], position: None, draft: "".to_owned(), path: path.clone(), max_len: 2, persist: true};
+    history.push("b");
+    history.push("x");
+    history.push("x");
+    history.push("d");
+    assert_eq!(history.entries, vec!["x", "d"]);
+    assert_eq!(history.older("s"), Some("d".to_owned()));
+    assert_eq!(history.older("d"), Some("x".to_owned()));
+    assert_eq!(history.older("x"), None);
+    assert_eq!(history.newer(), Some("d".to_owned()));
+    assert_eq!(history.newer(), Some("s".to_owned()));
+    assert_eq!(History::load(path.clone(), 2).entries, vec!["x", "d"]);
+    // a lowered `history_size` applies to the file right away
+    assert_eq!(History::load(path.clone(), 1).entries, vec!["d"]);
+    // a workspace's history isn't written to the file
+    history.persist = false;
+    history.push("b");
+    assert_eq!(History::load(path.clone(), 2).entries, vec!["x", "d"]);
+    history.set_max_len(1);
+    assert_eq!(history.entries, vec!["b"]);
+    _ = fs::remove_file(path);
+}
@@ -1,9 +1,23 @@
 use platform_dirs::AppDirs;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use ron::ser::{to_string_pretty, PrettyConfig};
-use ron::de::from_reader;
 use std::fs;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use crate::math::base::NumberBase;
+use crate::keymap::{Action, Chord, default_keymap};
+
+// the most fractional places minicalc formats, more would take too long to show or run out of memory
+pub const MAX_FRACTIONAL_PLACES: u32 = 100_000;
+// the most `ans` registers, every evaluation shifts all of them
+pub const MAX_ANS_REGISTERS: u32 = 1000;
+// the most commands and calculations kept, they're saved with every change
+pub const MAX_HISTORY_SIZE: u32 = 100_000;
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
@@ -11,6 +25,26 @@ pub struct Config {
     pub max_fractional_places: u32,
     #[serde(default = "defaults::base")]
     pub base: NumberBase,
+    #[serde(default = "defaults::percent_mode")]
+    pub percent_mode: bool,
+    #[serde(default = "defaults::chain_operations")]
+    pub chain_operations: bool,
+    #[serde(default = "defaults::repeat_on_enter")]
+    pub repeat_on_enter: bool,
+    #[serde(default = "defaults::ans_registers")]
+    pub ans_registers: u32,
+    #[serde(default = "defaults::persist_ans")]
+    pub persist_ans: bool,
+    #[serde(default = "defaults::history_size")]
+    pub history_size: u32,
+    #[serde(default = "defaults::keymap")]
+    pub keymap: BTreeMap<String, Action>,// chords like `Ctrl+Z` to actions laid over the default ones, `Unbound` takes one off, see `keymap::Action`
+    #[serde(default = "defaults::base_cycle")]
+    pub base_cycle: Vec<NumberBase>,// the bases `ToggleBase` goes through
+    #[serde(default = "defaults::autosave")]
+    pub autosave: bool,// write the open workspace, or the variables outside one, whenever they change and on exit
+    #[serde(default = "defaults::restore_session")]
+    pub restore_session: bool,// pick up the equation, base and percent mode from when minicalc was closed
 }
 
 macro_rules! default_ {
@@ -26,6 +60,20 @@ mod defaults {
     use super::NumberBase;
     default_!(max_fractional_places, u32);
     default_!(base, NumberBase);
+    default_!(percent_mode, bool);
+    default_!(chain_operations, bool);
+    default_!(repeat_on_enter, bool);
+    default_!(ans_registers, u32);
+    default_!(persist_ans, bool);
+    default_!(history_size, u32);
+    default_!(autosave, bool);
+    default_!(restore_session, bool);
+    pub fn keymap() -> super::BTreeMap<String, super::Action> {
+        Config::default().keymap
+    }
+    pub fn base_cycle() -> Vec<NumberBase> {
+        Config::default().base_cycle
+    }
 }
 
 impl Default for Config {
@@ -33,32 +81,474 @@ impl Default for Config {
         Self {
             max_fractional_places: 128,
             base: NumberBase::Decimal,
+            percent_mode: false,
+            chain_operations: true,
+            repeat_on_enter: true,
+            ans_registers: 9,
+            persist_ans: false,
+            history_size: 1000,
+            keymap: default_keymap(),
+            base_cycle: vec![NumberBase::Decimal, NumberBase::Hexadecimal, NumberBase::Binary],
+            autosave: false,
+            restore_session: false,
         }
     }
 }
 
-impl Config {
-    pub fn load() -> Self {
-        let dirs = AppDirs::new(Some("minicalc"), false).unwrap();
-        let config_dir = dirs.config_dir;
-        let _ = fs::create_dir_all(&config_dir);
-        let egui_config_path = config_dir.join("minicalc.cfg");
-        let file = fs::File::open(&egui_config_path);
-        let file = match file {
-            Ok(file) => {file},
-            Err(_) => {
-                _ = fs::write(egui_config_path, to_string_pretty(&Self::default(), PrettyConfig::default()).unwrap());
-                return Self::default()
+// set at startup from `--config-dir`, `--portable` and the environment, otherwise decided on first use
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// the environment variable that overrides the config directory
+pub const CONFIG_DIR_VAR: &str = "MINICALC_CONFIG_DIR";
+// the portable config directory next to the executable, used whenever it exists
+const PORTABLE_DIR: &str = "minicalc-config";
+
+// uses `dir` for config files from now on, must be called before anything reads the config
+pub fn set_config_dir(dir: PathBuf) {
+    _ = CONFIG_DIR.set(dir);
+}
+
+pub fn portable_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(PORTABLE_DIR))
+}
+
+// the config directory for `--config-dir` and `--portable`, see `resolve_config_dir`
+pub fn choose_config_dir(flag: Option<PathBuf>, portable: bool) -> PathBuf {
+    resolve_config_dir(flag, std::env::var_os(CONFIG_DIR_VAR), portable_dir(), portable)
+}
+
+// the first of `flag`, the `MINICALC_CONFIG_DIR` value `var`, the `portable` directory with `--portable` or whenever it exists,
+// and the user's config directory
+fn resolve_config_dir(flag: Option<PathBuf>, var: Option<OsString>, portable: Option<PathBuf>, portable_flag: bool) -> PathBuf {
+    if let Some(dir) = flag {return dir};
+    if let Some(dir) = var.filter(|dir| !dir.is_empty()) {return PathBuf::from(dir)};
+    if let Some(dir) = portable.filter(|dir| portable_flag || dir.is_dir()) {return dir};
+    AppDirs::new(Some("minicalc"), false).unwrap().config_dir
+}
+
+fn default_config_dir() -> PathBuf {
+    choose_config_dir(None, false)
+}
+
+// where config files and other per-user state live, created if missing
+pub fn config_dir() -> PathBuf {
+    let config_dir = CONFIG_DIR.get_or_init(default_config_dir).clone();
+    let _ = fs::create_dir_all(&config_dir);
+    config_dir
+}
+
+// points the config directory into the temp dir, for tests that read or write files in it
+#[cfg(test)]
+pub fn test_config_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join("minicalc-test-config");
+    set_config_dir(dir.clone());
+    assert_eq!(config_dir(), dir, "a test used the config directory before setting it");
+    dir
+}
+
+// notices when a file changes on disk by polling its modification time
+pub struct FileWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl FileWatch {
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(path: PathBuf) -> Self {
+        let modified = Self::modified(&path);
+        Self {path, modified, last_check: Instant::now()}
+    }
+
+    fn modified(path: &PathBuf) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    // true once per change, checks at most every `INTERVAL`
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < Self::INTERVAL {return false};
+        self.last_check = Instant::now();
+        self.forget_changes()
+    }
+
+    // takes in the current modification time, e.g. after writing the file ourselves
+    pub fn forget_changes(&mut self) -> bool {
+        let modified = Self::modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+// a problem with a config file, reported instead of quietly using defaults
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub position: Option<(usize, usize)>,// line and column
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ConfigError {
+    // an error about `field`, placed at the line it's on in `source`
+    pub fn in_field(path: &Path, source: &str, field: &str, message: String) -> Self {
+        let position = Self::field_line(source, field).map(|line| (line + 1, 1));
+        Self {path: path.to_owned(), position, field: Some(field.to_owned()), message}
+    }
+
+    // an error about `text` within `field`, like one chord of the keymap, placed where `text` is in `source`
+    pub fn in_field_at(path: &Path, source: &str, field: &str, text: &str, message: String) -> Self {
+        let start = Self::field_line(source, field).unwrap_or(0);
+        let position = source.lines().enumerate().skip(start).find_map(|(i, line)| line.find(text).map(|col| (i + 1, col + 1)));
+        Self {path: path.to_owned(), position, field: Some(field.to_owned()), message}
+    }
+
+    // the index of the line starting with `field:` in `source`
+    fn field_line(source: &str, field: &str) -> Option<usize> {
+        let prefix = format!("{field}:");
+        source.lines().position(|line| line.trim_start().starts_with(prefix.as_str()))
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some((line, col)) = self.position {write!(f, ":{line}:{col}")?};
+        if let Some(field) = &self.field {write!(f, ": {field}")?};
+        write!(f, ": {}", self.message)
+    }
+}
+
+// the field whose value is at `line` in `source`, the nearest `name:` at or above it
+fn field_at(source: &str, line: usize) -> Option<String> {
+    source.lines().take(line).collect::<Vec<&str>>().into_iter().rev().find_map(|line| {
+        let (name, _) = line.trim_start().split_once(':')?;
+        let is_name = !name.is_empty() && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric());
+        if is_name {Some(name.to_owned())} else {None}
+    })
+}
+
+// parses a RON config file along with its text, None if there is no file yet
+pub fn read_file<T: DeserializeOwned>(path: &Path) -> Result<Option<(T, String)>, ConfigError> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ConfigError {path: path.to_owned(), position: None, field: None, message: e.to_string()}),
+    };
+    match ron::from_str::<T>(&source) {
+        Ok(config) => Ok(Some((config, source))),
+        Err(e) => {
+            let (line, col) = (e.position.line, e.position.col);
+            Err(ConfigError {path: path.to_owned(), position: Some((line, col)), field: field_at(&source, line), message: e.code.to_string()})
+        },
+    }
+}
+
+// writes `config` to `path` unless the file there doesn't parse, which would lose everything in it but the defaults
+pub fn save_file<T: Serialize + DeserializeOwned>(path: &Path, config: &T) -> Result<(), String> {
+    if let Err(e) = read_file::<T>(path) {
+        return Err(format!("not saved until the config is fixed: {e}"));
+    }
+    fs::write(path, to_string_pretty(config, PrettyConfig::default()).unwrap()).map_err(|_| format!("failed writing config '{}'", path.display()))
+}
+
+// `path` with `suffix` added to the file name, like `minicalc-vars.bak.1`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// the files `write_atomic` has backed up since minicalc started
+static BACKED_UP: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+// how many sessions back the backups of a file go
+const BACKUPS: usize = 3;
+
+// writes a temp file and renames it over `path`, so an interrupted write leaves the old file intact,
+// the file as it was before the first write of the session is kept as `<path>.bak.1`, older ones shift up to `.bak.3`
+pub fn write_atomic(path: &Path, data: &str) -> std::io::Result<()> {
+    let temp = with_suffix(path, ".tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(data.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    let mut backed_up = BACKED_UP.lock().unwrap_or_else(|e| e.into_inner());
+    if !backed_up.iter().any(|backup| backup == path) {
+        if path.exists() {
+            for i in (1..BACKUPS).rev() {
+                let backup = with_suffix(path, &format!(".bak.{i}"));
+                if backup.exists() {
+                    fs::rename(backup, with_suffix(path, &format!(".bak.{}", i + 1)))?;
+                }
             }
-        };
-        let conf = from_reader::<fs::File, Self>(file);
-        match conf {
-            Ok(conf) => {
+            fs::copy(path, with_suffix(path, ".bak.1"))?;
+        }
+        backed_up.push(path.to_owned());
+    }
+    fs::rename(&temp, path)
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        config_dir().join("minicalc.cfg")
+    }
+
+    // the config file as it is now, without writing back defaults
+    pub fn read() -> Result<Self, Vec<ConfigError>> {
+        let path = Self::path();
+        match read_file::<Self>(&path) {
+            Ok(Some((config, source))) => {
+                let errors = config.file_errors(&path, &source);
+                if errors.is_empty() {Ok(config)} else {Err(errors)}
+            },
+            Ok(None) => Err(vec![ConfigError {path, position: None, field: None, message: "no such file".to_owned()}]),
+            Err(e) => Err(vec![e]),
+        }
+    }
+
+    // the config, with defaults for a missing file and the errors that made it fall back to them
+    pub fn load() -> (Self, Vec<ConfigError>) {
+        let path = Self::path();
+        match read_file::<Self>(&path) {
+            Ok(Some((mut conf, source))) => {
                 // write back default values of any fields not present
-                _ = fs::write(egui_config_path, to_string_pretty(&conf, PrettyConfig::default()).unwrap());
-                conf
+                _ = conf.save();
+                let errors = conf.file_errors(&path, &source);
+                if conf.max_fractional_places > MAX_FRACTIONAL_PLACES {
+                    conf.max_fractional_places = defaults::max_fractional_places();
+                }
+                if conf.ans_registers > MAX_ANS_REGISTERS {
+                    conf.ans_registers = defaults::ans_registers();
+                }
+                if conf.history_size > MAX_HISTORY_SIZE {
+                    conf.history_size = defaults::history_size();
+                }
+                (conf, errors)
+            },
+            Ok(None) => {
+                _ = Self::default().save();
+                (Self::default(), vec![])
+            },
+            Err(e) => (Self::default(), vec![e]),
+        }
+    }
+
+    // values that parse but can't be used, like a keymap chord with an unknown key
+    pub fn file_errors(&self, path: &Path, source: &str) -> Vec<ConfigError> {
+        let chords = self.keymap.keys().filter_map(|chord| {
+            let message = Chord::from_str(chord).err()?;
+            let quoted = ron::to_string(chord).unwrap_or_default();
+            Some(ConfigError::in_field_at(path, source, "keymap", &quoted, message))
+        });
+        let fields = self.invalid_fields().into_iter().map(|(field, message)| ConfigError::in_field(path, source, field, message));
+        chords.chain(fields).collect()
+    }
+
+    // fields with values that parse but can't be used, like a precision that would take too long to show
+    pub fn invalid_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![];
+        if self.max_fractional_places > MAX_FRACTIONAL_PLACES {
+            fields.push(("max_fractional_places", format!("{} is more than the most places, {MAX_FRACTIONAL_PLACES}", self.max_fractional_places)));
+        }
+        if self.ans_registers > MAX_ANS_REGISTERS {
+            fields.push(("ans_registers", format!("{} is more than the most registers, {MAX_ANS_REGISTERS}", self.ans_registers)));
+        }
+        if self.history_size > MAX_HISTORY_SIZE {
+            fields.push(("history_size", format!("{} is more than the longest history, {MAX_HISTORY_SIZE}", self.history_size)));
+        }
+        fields
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.invalid_fields().first() {
+            Some((name, message)) => Err(format!("bad value for {name}: {message}")),
+            None => Ok(()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        save_file(&Self::path(), self)
+    }
+}
+
+// the fields of a config with their values as RON, by name, split from the config written as `(name:value,...)`,
+// `ron::Value` would be simpler but it loses the names of enum variants like `Hexadecimal`
+fn fields<T: Serialize>(config: &T) -> Vec<(String, String)> {
+    let text = ron::to_string(config).unwrap_or_default();
+    let inner = text.strip_prefix('(').and_then(|text| text.strip_suffix(')')).unwrap_or("");
+    let mut fields = vec![];
+    let (mut depth, mut quoted, mut escaped, mut start) = (0, false, false, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '(' | '[' | '{' if !quoted => depth += 1,
+            ')' | ']' | '}' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                fields.push(&inner[start..i]);
+                start = i + 1;
             },
-            Err(_) => Self::default(),
-        } 
+            _ => {},
+        }
+    }
+    fields.push(&inner[start..]);
+    fields.into_iter().filter_map(|field| {
+        let (name, value) = field.split_once(':')?;
+        Some((name.to_owned(), value.to_owned()))
+    }).collect()
+}
+
+pub fn field_names<T: Serialize>(config: &T) -> Vec<String> {
+    fields(config).into_iter().map(|(name, _)| name).collect()
+}
+
+// `key = value` for every field, or only `key`
+pub fn get_fields<T: Serialize>(config: &T, key: Option<&str>) -> Vec<String> {
+    fields(config).into_iter()
+        .filter(|(name, _)| key.is_none_or(|key| key == name))
+        .map(|(name, value)| format!("{name} = {value}"))
+        .collect()
+}
+
+// a copy of `config` with `key` set to `value`, which is RON like `128`, `true` or `Hexadecimal`
+pub fn set_field<T: Serialize + DeserializeOwned>(config: &T, key: &str, value: &str) -> Result<T, String> {
+    let fields = fields(config);
+    if !fields.iter().any(|(name, _)| name == key) {
+        return Err(format!("no setting '{key}'"));
+    }
+    let with = |value: &str| {
+        let fields: Vec<String> = fields.iter().map(|(name, field)| {
+            format!("{}:{}", name, if name == key {value} else {field})
+        }).collect();
+        ron::from_str::<T>(format!("({})", fields.join(",")).as_str()).map_err(|e| e.code.to_string())
+    };
+    match with(value) {
+        Ok(config) => Ok(config),
+        // strings like colors don't need quotes
+        Err(e) => with(ron::to_string(value).unwrap().as_str()).map_err(|_| format!("bad value for {key}: {e}")),
+    }
+}
+
+#[test]
+fn file_watch_test() {
+    let path = std::env::temp_dir().join("minicalc-file-watch-test");
+    fs::write(&path, "(base: Decimal)").unwrap();
+    let mut watch = FileWatch::new(path.clone());
+    // checks wait for the interval
+    fs::write(&path, "(base: Hexadecimal)").unwrap();
+    fs::File::options().write(true).open(&path).unwrap().set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+    assert!(!watch.changed());
+    watch.last_check -= FileWatch::INTERVAL;
+    assert!(watch.changed());
+    // each change is seen once
+    watch.last_check -= FileWatch::INTERVAL;
+    assert!(!watch.changed());
+    // writing the file ourselves isn't a change to reload
+    fs::File::options().write(true).open(&path).unwrap().set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+    watch.forget_changes();
+    watch.last_check -= FileWatch::INTERVAL;
+    assert!(!watch.changed());
+    _ = fs::remove_file(path);
+}
+
+#[test]
+fn config_dir_test() {
+    let temp = std::env::temp_dir();
+    let (flag, var, portable) = (temp.join("minicalc-flag"), temp.join("minicalc-var"), temp.join("minicalc-portable-test"));
+    let user = AppDirs::new(Some("minicalc"), false).unwrap().config_dir;
+    let resolve = |flag: Option<&PathBuf>, var: Option<&PathBuf>, portable_flag| {
+        resolve_config_dir(flag.cloned(), var.map(|var| var.clone().into_os_string()), Some(portable.clone()), portable_flag)
+    };
+    _ = fs::remove_dir(&portable);
+    assert_eq!(resolve(Some(&flag), Some(&var), true), flag);
+    assert_eq!(resolve(None, Some(&var), true), var);
+    assert_eq!(resolve(None, Some(&PathBuf::new()), false), user);
+    // the portable directory is used with `--portable`, or without it once it exists
+    assert_eq!(resolve(None, None, true), portable);
+    assert_eq!(resolve(None, None, false), user);
+    fs::create_dir_all(&portable).unwrap();
+    assert_eq!(resolve(None, None, false), portable);
+    assert_eq!(resolve(None, Some(&var), false), var);
+    _ = fs::remove_dir(&portable);
+}
+
+#[test]
+fn write_atomic_test() {
+    let path = std::env::temp_dir().join("minicalc-write-atomic-test");
+    fs::write(&path, "old").unwrap();
+    write_atomic(&path, "new").unwrap();
+    write_atomic(&path, "newer").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "newer");
+    // only the file from before the first write is backed up
+    let backup = |i: usize| fs::read_to_string(with_suffix(&path, &format!(".bak.{i}"))).ok();
+    assert_eq!(backup(1).as_deref(), Some("old"));
+    assert!(!with_suffix(&path, ".tmp").exists());
+    // each session's first write shifts the backups, keeping the last `BACKUPS`
+    for session in ["first", "second", "third"] {
+        BACKED_UP.lock().unwrap().retain(|backed_up| backed_up != &path);
+        write_atomic(&path, session).unwrap();
+    }
+    assert_eq!((1..=4).map(backup).collect::<Vec<_>>(), vec![Some("second".to_owned()), Some("first".to_owned()), Some("newer".to_owned()), None]);
+    for i in 1..=BACKUPS {
+        _ = fs::remove_file(with_suffix(&path, &format!(".bak.{i}")));
     }
+    _ = fs::remove_file(path);
+}
+
+#[test]
+fn set_field_test() {
+    let config = Config::default();
+    assert_eq!(get_fields(&config, Some("max_fractional_places")), vec!["max_fractional_places = 128"]);
+    let config = set_field(&config, "max_fractional_places", "1000").unwrap();
+    assert_eq!(config.max_fractional_places, 1000);
+    let config = set_field(&config, "base", "Hexadecimal").unwrap();
+    assert_eq!(config.base, NumberBase::Hexadecimal);
+    assert_eq!(config.max_fractional_places, 1000);
+    assert!(set_field(&config, "max_fractional_places", "-5").is_err());
+    let too_precise = set_field(&config, "max_fractional_places", "4000000000").unwrap();
+    assert_eq!(too_precise.validate(), Err("bad value for max_fractional_places: 4000000000 is more than the most places, 100000".to_owned()));
+    let too_many = set_field(&config, "ans_registers", "4000000000").unwrap();
+    assert_eq!(too_many.validate(), Err("bad value for ans_registers: 4000000000 is more than the most registers, 1000".to_owned()));
+    let too_long = set_field(&config, "history_size", "4000000000").unwrap();
+    assert_eq!(too_long.validate(), Err("bad value for history_size: 4000000000 is more than the longest history, 100000".to_owned()));
+    assert!(set_field(&config, "ans_registers", "1000").unwrap().validate().is_ok());
+    assert!(set_field(&config, "persist_ans", "yes").is_err());
+    assert_eq!(set_field(&config, "colour", "1").err(), Some("no setting 'colour'".to_owned()));
+    // nested values and strings with separators in them stay whole
+    let config = set_field(&config, "keymap", "{\"Ctrl+,\": Undo, \"(\": Copy}").unwrap();
+    assert_eq!(get_fields(&config, Some("keymap")), vec!["keymap = {\"(\":Copy,\"Ctrl+,\":Undo}"]);
+    assert_eq!(field_names(&config).len(), 12);
+}
+
+#[test]
+fn save_file_test() {
+    let path = std::env::temp_dir().join("minicalc-save-file-test");
+    let malformed = "(\n    max_fractional_places: 1000,\n    base: Octal,\n)";
+    fs::write(&path, malformed).unwrap();
+    let config = Config::default();
+    assert!(save_file(&path, &config).unwrap_err().starts_with("not saved until the config is fixed: "));
+    assert_eq!(fs::read_to_string(&path).unwrap(), malformed);
+    fs::write(&path, "(max_fractional_places: 1000)").unwrap();
+    assert_eq!(save_file(&path, &config), Ok(()));
+    assert_eq!(read_file::<Config>(&path).unwrap().map(|(config, _)| config.max_fractional_places), Some(128));
+    _ = fs::remove_file(path);
 }
+
+#[test]
+fn config_error_test() {
+    let path = std::env::temp_dir().join("minicalc-config-error-test");
+    fs::write(&path, "(\n    max_fractional_places: 128,\n    base: Octal,\n)").unwrap();
+    let e = read_file::<Config>(&path).err().unwrap();
+    assert_eq!(e.position.map(|(line, _)| line), Some(3));
+    assert_eq!(e.field.as_deref(), Some("base"));
+
+    let source = "(\n    keymap: {\n        \"Ctrl+Z\": Undo,\n        \"Hyper+Q\": Undo,\n    },\n    max_fractional_places: 1000000,\n)";
+    let config: Config = ron::from_str(source).unwrap();
+    let errors = config.file_errors(&path, source);
+    assert_eq!(errors.iter().map(|e| (e.position, e.field.as_deref())).collect::<Vec<_>>(), vec![(Some((4, 9)), Some("keymap")), (Some((6, 1)), Some("max_fractional_places"))]);
+    _ = fs::remove_file(path);
+}
+
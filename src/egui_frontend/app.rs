@@ -1,4 +1,3 @@
-use crate::math::parsefmt;
 use crate::minicalc;
 use eframe::egui::Response;
 use eframe::emath::Align2;
@@ -8,12 +7,16 @@ use eframe::{
     emath::Align,
     epaint::Color32,
 };
-use std::collections::HashMap;
-use std::fs;
-use std::path::Path;
-use arboard::Clipboard;
+use crate::keymap::{Action, Chord, Key};
+use std::path::PathBuf;
 use std::time::Duration;
 use super::config::EguiConfig;
+use crate::config::{self, FileWatch};
+
+const CONFIG_ERROR_ALERT_TIME: f32 = 10.;
+
+// commands only the egui frontend understands, for completion
+const EGUI_COMMANDS: &[&str] = &["D", "decorated", "border", "t", "top", "a"];
 
 pub struct AppState {
     pub state: minicalc::State,
@@ -23,89 +26,16 @@ pub struct AppState {
     pub typed: bool,
     pub alert: String,
     pub alert_timer: f32,
+    pub startup_errors: Option<String>,// config problems from loading, kept above other alerts until they time out
+    pub config_watch: FileWatch,// egui.cfg, reloaded when it changes
 }
 
 impl AppState {
     fn display(&mut self) -> String {
-        if self.state.command.is_some() {
-            format!(":{}", self.state.command.as_ref().unwrap())
-        } else {
-            if self.state.cached_equation_display.is_some() {
-                self.state.cached_equation_display.clone().unwrap()
-            } else {
-                let display = self.state.equation.display(self.state.base.clone(), self.state.config.max_fractional_places);
-                self.state.cached_equation_display = Some(display.clone());
-                display
-            }
-        }
-    }
-    fn try_type_single(&mut self, char: char) {
-        self.state.equation.try_type_single(char.to_uppercase().next().unwrap().to_string().as_str(), self.state.base.clone(), self.state.config.max_fractional_places) 
-    }
-    fn enter_command_entry(&mut self, command: String) {
-        self.state.command = Some(command);
-    }
-    fn enter_equation_entry(&mut self) {
-        self.state.command = None;
-    }
-    fn write_vars(&mut self) {
-        let mut out = String::new();
-        for (name, n) in self.state.variables.iter() {
-            out += format!("{}\n{}\n",name,n).as_str();
-        }
-        let res = fs::write(self.state.vars_path.clone(), out);
-        if res.is_err() {
-            self.alert(format!("failed writing vars '{}'",self.state.vars_path), self.config.vars_alert_time);
-        } else {
-            self.alert(format!("wrote vars '{}'", self.state.vars_path), self.config.vars_alert_time);
-        }
-    }
-    pub fn read_vars(&mut self) {
-        let data = fs::read_to_string(self.state.vars_path.clone());
-        if data.is_err() {
-            self.alert(format!("no file '{}'", self.state.vars_path), self.config.vars_alert_time);
-            return
-        };
-        self.state.variables = HashMap::new();
-        let data = data.unwrap();
-        let lines: Vec<&str> = data.lines().collect();
-        for pair in lines.chunks(2) {
-            let n = parsefmt::parse(pair[1].to_owned());
-            let n = match n {
-                Some(n) => {n},
-                None => {continue},
-            };
-            self.state.variables.insert(pair[0].to_owned(), n);
-        }
-        self.alert(format!("read vars '{}'", self.state.vars_path), self.config.vars_alert_time);
-    }
-    fn delete_one(&mut self) {
-        if self.state.command.is_some() {
-            let c = self.state.command.as_mut().unwrap();
-            if c.len() > 0 {
-                *c = c[..c.len()-1].to_owned();
-            } else {
-                self.state.command = None;
-            }
-        } else {
-            self.state.equation.delete_one_mut(self.state.base.clone(), self.state.config.max_fractional_places);
-        }
-        self.state.cached_equation_display = None;
+        self.state.display()
     }
     fn type_string(&mut self, text: String) {
-        let text = text.replace("\n", "");
-        if self.state.command.is_some() {
-            *self.state.command.as_mut().unwrap() += text.as_str();
-        } else {
-            if text.starts_with(":") {
-                self.state.command = Some("".to_owned());
-                self.type_string(text[1..].to_owned());
-            }
-            for char in text.chars() {
-                self.try_type_single(char);
-            }
-        }
-        self.state.cached_equation_display = None;// invalidate the cached display
+        self.state.type_string(text)
     }
     fn execute_command(&mut self) {
         let command = self.state.command.clone().unwrap_or("".to_owned());
@@ -120,6 +50,10 @@ impl AppState {
             "d" | "decimal" | "b10" => {
                 self.alert("decimal".to_owned(), self.config.base_change_alert_time);
             },
+            "pct" | "percent" => {
+                let msg = if self.state.percent_mode {"modulo"} else {"percent"};
+                self.alert(msg.to_owned(), self.config.base_change_alert_time);
+            },
             "D" | "decorated" | "border" => {self.window_decorated = !self.window_decorated},
             "t" | "top" => {self.always_on_top = !self.always_on_top},
             "" => {},// skip this case before we do any other logic
@@ -136,29 +70,17 @@ impl AppState {
                             "l" | "left" | "r" | "right" | "R" | "result" => {},
                             _ => {break 's_case}
                         };
-                        let r = args.next();
-                        let name = match r {
-                            Some(name) => {name},
-                            None => {break 's_case}
-                        };
+                        if args.next().is_none() {break 's_case};// no name
 
+                        // storing itself is left to the common impl
                         match side {
-                            "l" | "left" => {
-                                self.state.variables.insert(name.to_owned(), self.state.equation.left.clone());
-                            },
-                            "r" | "right" => {
-                                if self.state.equation.editing_left() {
-                                    self.alert("no right operand to store".to_owned(), self.config.vars_alert_time);
-                                    break 's_case
-                                };
-                                self.state.variables.insert(name.to_owned(), self.state.equation.right.clone().unwrap());
+                            "r" | "right" if self.state.equation.editing_left() => {
+                                self.alert("no right operand to store".to_owned(), self.config.vars_alert_time);
                             },
                             "R" | "result" => {
-                                let r = self.state.equation.eval();
-                                if let Some(result) = r {
-                                    self.state.variables.insert(name.to_owned(), result.left);
-                                } else {
-                                    self.alert("can't store equation result".to_owned(), self.config.vars_alert_time);
+                                let r = self.state.equation.eval(&self.state.variables);
+                                if let Err(e) = r {
+                                    self.alert(format!("can't store equation result: {e}"), self.config.vars_alert_time);
                                 }
                             },
                             _ => {}
@@ -185,46 +107,53 @@ impl AppState {
                             break 'l_case
                         };
 
-                        match side {
-                            "l" | "left" => {
-                                self.state.equation.left = self.state.variables.get(name).unwrap().clone();
-                                self.state.cached_equation_display = None;
-                            },
-                            "r" | "right" => {
-                                if self.state.equation.editing_left() {
-                                    self.alert("no right operand to load into".to_owned(), self.config.vars_alert_time);
-                                    break 'l_case
-                                };
-                                self.state.equation.right = Some(self.state.variables.get(name).unwrap().clone());
-                                self.state.cached_equation_display = None;
-                            },
-                            _ => {}
+                        // loading itself is left to the common impl
+                        if let "r" | "right" = side {
+                            if self.state.equation.editing_left() {
+                                self.alert("no right operand to load into".to_owned(), self.config.vars_alert_time);
+                            }
                         }
                     },
-                    "p" | "path" => {
-                        let remaining: Vec<&str> = args.collect();
-                        let path = remaining.join(" ");
-                        self.state.vars_path = path;
-                    },
-                    "r" | "read" => 'r_case: {
-                        let remaining: Vec<&str> = args.collect();
-                        let path: String = remaining.join(" ");
-                        let path = Path::new(path.as_str());
-                        if !Path::exists(&path){break 'r_case};
-                        self.state.vars_path = path.to_string_lossy().into();
-                        self.read_vars();
-                    },
-                    "w" | "write" => {
-                        let remaining: Vec<&str> = args.collect();
-                        let path = remaining.join(" ");
-                        self.state.vars_path = path;
-                        self.write_vars();
-                    },
                     "a" => {
                         let remaining: Vec<&str> = args.collect();
                         let text = remaining.join(" ");
                         self.alert(text, 5.);
                     }
+                    // settings of the egui config, the common impl only keeps the history for these
+                    "get" | "set" | "set!" => 'set_case: {
+                        let key = args.next().unwrap_or("");
+                        let name = command.split(' ').next().unwrap_or("");
+                        if name == "get" && key.is_empty() {
+                            // both configs
+                            self.state.execute_command();
+                            let lines = config::get_fields(&self.config, None);
+                            self.state.listing.get_or_insert(vec![]).extend(lines);
+                            return;
+                        }
+                        if !self.state.frontend_settings.iter().any(|name| name == key) {break 'set_case};
+                        if name == "get" {
+                            self.alert(config::get_fields(&self.config, Some(key)).join(""), self.config.vars_alert_time);
+                        } else {
+                            let value: Vec<&str> = args.collect();
+                            let config = config::set_field(&self.config, key, value.join(" ").as_str());
+                            match config.and_then(|config| config.validate().map(|_| config)) {
+                                Ok(config) => {
+                                    self.config = config.clone().with_try_parse_colors();
+                                    self.window_decorated = self.config.window_decorated;
+                                    self.always_on_top = self.config.always_on_top;
+                                    let saved = if name == "set!" {self.config.save()} else {Ok(())};
+                                    match saved {
+                                        Ok(()) => {
+                                            if name == "set!" {self.config_watch.forget_changes();}
+                                            self.alert(config::get_fields(&self.config, Some(key)).join(""), self.config.vars_alert_time);
+                                        },
+                                        Err(e) => self.alert(e, self.config.vars_alert_time),
+                                    }
+                                },
+                                Err(e) => self.alert(e, self.config.vars_alert_time),
+                            }
+                        }
+                    },
                     _ => {},
                 }
             },
@@ -232,21 +161,57 @@ impl AppState {
         // fallthru to common impl
         self.state.execute_command();
     }
-    fn copy_equation(&mut self) {
-        let cbrd = Clipboard::new();
-        let mut cbrd = match cbrd {
-            Ok(cbrd) => cbrd,
-            Err(e) => {
-                println!("error while copying: {:?}", e);
-                return
-            }
-        };
-        let text = self.state.equation.display(self.state.base.clone(), self.state.config.max_fractional_places);
-        let res = cbrd.set_text(text);
-        if res.is_err() {
-            self.alert("failed to copy".to_owned(), self.config.copy_eq_alert_time);
+    // runs a keymap action, with the parts only this frontend knows about
+    fn perform(&mut self, ctx: &egui::Context, action: Action) {
+        match action {
+            Action::Evaluate if self.state.command.is_some() => {
+                self.execute_command();
+                self.apply_window_settings(ctx);
+                if self.state.exiting {
+                    // exiting skips `on_exit`
+                    self.state.before_exit();
+                    std::process::exit(0x0)
+                }
+            },
+            Action::Complete => self.state.complete_command(EGUI_COMMANDS),
+            Action::Copy => self.copy_equation(),
+            Action::ToggleBase | Action::BaseBinary | Action::BaseDecimal | Action::BaseHexadecimal => {
+                self.state.perform(action);
+                self.alert(self.state.base.name().to_owned(), self.config.base_change_alert_time);
+            },
+            action => {self.state.perform(action);},
+        }
+    }
+    fn apply_window_settings(&self, ctx: &egui::Context) {
+        ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(self.window_decorated));
+        ctx.send_viewport_cmd(if self.always_on_top { 
+            egui::ViewportCommand::WindowLevel(egui::WindowLevel::AlwaysOnTop) 
         } else {
-            self.alert("copied equation".to_owned(), self.config.copy_eq_alert_time);
+            egui::ViewportCommand::WindowLevel(egui::WindowLevel::Normal)
+        });
+    }
+    // re-reads egui.cfg if it changed on disk, keeping the current config if it's malformed
+    fn reload_config(&mut self, ctx: &egui::Context) {
+        if !self.config_watch.changed() {return};
+        match EguiConfig::read() {
+            Ok(config) => {
+                self.config = config;
+                self.window_decorated = self.config.window_decorated;
+                self.always_on_top = self.config.always_on_top;
+                self.apply_window_settings(ctx);
+                self.typed = true;// fit the window to the new sizes
+                self.alert("reloaded config".to_owned(), self.config.vars_alert_time);
+            },
+            Err(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                self.alert(format!("config not reloaded: {}", errors.join("\n")), self.config.vars_alert_time);
+            },
+        }
+    }
+    fn copy_equation(&mut self) {
+        self.state.copy_equation();
+        if let Some(message) = self.state.message.take() {
+            self.alert(message, self.config.copy_eq_alert_time);
         }
     }
 
@@ -266,8 +231,53 @@ impl AppState {
     }
 
     pub fn alert(&mut self, msg: String, t: f32) {
-        self.alert = msg;
-        self.alert_timer = t;
+        match &self.startup_errors {
+            Some(errors) if self.alert_timer > 0. => {
+                self.alert = format!("{errors}\n{msg}");
+                self.alert_timer = self.alert_timer.max(t);
+            },
+            _ => {
+                self.startup_errors = None;
+                self.alert = msg;
+                self.alert_timer = t;
+            },
+        }
+    }
+
+    fn draw_memory(&mut self, ctx: &egui::Context, screen: Rect) {
+        let memory = match self.state.memory() {
+            Some(memory) => memory.clone(),
+            None => return,
+        };
+        let text = format!("M {}", self.state.format(&memory));
+        egui::Area::new("memory").anchor(Align2::LEFT_TOP, [2.,0.]).show(ctx, |ui| {
+            let size = screen.height().min(5000.) * 0.2;
+            ui.add(Label::new(egui::RichText::new(text).size(size)).wrap(false));
+        });
+    }
+
+    fn draw_base(&self, ctx: &egui::Context, screen: Rect) {
+        egui::Area::new("base").anchor(Align2::RIGHT_TOP, [-2.,0.]).show(ctx, |ui| {
+            let size = screen.height().min(5000.) * 0.2;
+            ui.add(Label::new(egui::RichText::new(self.state.base.badge()).size(size)).wrap(false));
+        });
+    }
+
+    fn draw_listing(&self, ctx: &egui::Context, screen: Rect) {
+        let listing = match &self.state.listing {
+            Some(listing) => listing,
+            None => return,
+        };
+        egui::Area::new("listing").anchor(Align2::LEFT_TOP, [0.,0.]).show(ctx, |ui| {
+            egui::Frame::none().fill(self.config.colors.alert_bg_color).show(ui, |ui| {
+                ui.set_min_size(screen.size());
+                egui::ScrollArea::vertical().max_height(screen.height()).show(ui, |ui| {
+                    for line in listing {
+                        ui.add(Label::new(line).wrap(false));
+                    }
+                });
+            });
+        });
     }
 
     fn draw_alert(&self, ctx: &egui::Context, screen: Rect) {
@@ -283,51 +293,105 @@ impl AppState {
 
 impl Default for AppState {
     fn default() -> Self {
-        let conf = EguiConfig::load();
-        Self { 
-            state: minicalc::State::default(),
-            window_decorated: conf.window_decorated, 
+        let (conf, errors) = EguiConfig::load();
+        let state = minicalc::State {frontend_settings: config::field_names(&conf), ..Default::default()};
+        let errors: Vec<String> = state.config_errors.iter().chain(errors.iter()).map(|e| e.to_string()).collect();
+        Self {
+            config_watch: FileWatch::new(EguiConfig::path()),
+            ..Self::new(state, conf, errors)
+        }
+    }
+}
+
+impl AppState {
+    // an app around `state` that watches no config file, `errors` are shown as soon as it starts
+    pub fn new(state: minicalc::State, conf: EguiConfig, errors: Vec<String>) -> Self {
+        // config problems stay up a while so they can be read
+        let startup_errors = if errors.is_empty() {None} else {Some(errors.join("\n"))};
+        let alert_timer = if errors.is_empty() {0.} else {CONFIG_ERROR_ALERT_TIME};
+        Self {
+            state,
+            window_decorated: conf.window_decorated,
             always_on_top: conf.always_on_top,
             config: conf,
             typed: false,
-            alert: "".to_owned(),
-            alert_timer: 0.,
+            alert: startup_errors.clone().unwrap_or_default(),
+            alert_timer,
+            startup_errors,
+            config_watch: FileWatch::new(PathBuf::new()),
         }
     }
-}
-impl eframe::App for AppState {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    // everything `update` does, which doesn't need the frame
+    fn show(&mut self, ctx: &egui::Context) {
         let visuals = Visuals {
             panel_fill: self.config.colors.bg_color,
             override_text_color: Some(self.config.colors.text_color),
             ..Default::default()
         };
         ctx.set_visuals(visuals);
-        for event in ctx.input(|i| i.events.iter().cloned().collect::<Vec<egui::Event>>()) {
+        self.reload_config(ctx);
+        self.state.reload_config();
+        ctx.request_repaint_after(Duration::from_secs(1));// keep polling the config files
+        if self.state.poll_formats() {
+            self.typed = true;
+        }
+        if self.state.format_cache.is_pending() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        }
+        for event in ctx.input(|i| i.events.to_vec()) {
             match event {
                 egui::Event::Text(t) => {
                     self.typed = true;
+                    self.state.listing = None;
+                    // a single typed character can be bound, like `:`
+                    let mut chars = t.chars();
+                    if let (Some(c), None) = (chars.next(), chars.next()) {
+                        if let Some(action) = self.state.key_action(&Chord::new(Key::Char(c), false, false, false)) {
+                            self.perform(ctx, action);
+                            continue;
+                        }
+                    }
                     self.type_string(t)
                 },
-                egui::Event::Paste(t) => {
-                    self.typed = true;
-                    self.type_string(t)
-                },
-                egui::Event::PointerButton { pos: _, button: _, pressed, modifiers: _ } => {
-                    if pressed {
-                        ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag)
+                egui::Event::Key { key, pressed: true, modifiers, .. } => {
+                    let chord = match chord(key, modifiers) {
+                        Some(chord) => chord,
+                        None => continue,
+                    };
+                    if let Some(action) = self.state.key_action(&chord) {
+                        self.typed = true;
+                        self.perform(ctx, action);
                     }
                 },
+                egui::Event::PointerButton { pos: _, button: _, pressed: true, modifiers: _ } => {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag)
+                },
+                // egui turns the platform's copy and paste shortcuts into these instead of key events,
+                // they do whatever Ctrl+C and Ctrl+V are bound to
                 egui::Event::Copy => {
-                    self.copy_equation();
+                    if let Some(action) = self.state.key_action(&Chord::new(Key::Char('C'), true, false, false)) {
+                        self.typed = true;
+                        self.perform(ctx, action);
+                    }
+                },
+                egui::Event::Paste(text) => {
+                    match self.state.key_action(&Chord::new(Key::Char('V'), true, false, false)) {
+                        Some(Action::Paste) => {
+                            self.typed = true;
+                            self.state.listing = None;
+                            self.type_string(text)
+                        },
+                        Some(action) => {
+                            self.typed = true;
+                            self.perform(ctx, action);
+                        },
+                        None => {},
+                    }
                 },
                 _ => {},
             }
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::Backspace)) {
-            self.typed = true;
-            self.delete_one();
-        }
+        self.state.autosave();
         let rect = ctx.input(|i| i.viewport().inner_rect);
         let size = match rect {
             Some(rect) => {
@@ -335,7 +399,8 @@ impl eframe::App for AppState {
             },
             None => {12.}
         };
-        let display = egui::RichText::new(self.display()).size(size);
+        let text = self.display();
+        let display = egui::RichText::new(text.as_str()).size(size);
         let cursor_blink = ctx.input(|i| (i.time % 1.) > 0.5 );
         let cursor = egui::RichText::new("|").size(size).color(if cursor_blink {Color32::WHITE} else {Color32::TRANSPARENT});
         if self.state.command.is_some() {
@@ -350,40 +415,91 @@ impl eframe::App for AppState {
                     ui.label(cursor);
                 });
             });
-            if ctx.input(|i| i.key_down(egui::Key::Enter)) {
-                self.execute_command();
-                ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(self.window_decorated));
-                ctx.send_viewport_cmd(if self.always_on_top { 
-                    egui::ViewportCommand::WindowLevel(egui::WindowLevel::AlwaysOnTop) 
-                } else {
-                    egui::ViewportCommand::WindowLevel(egui::WindowLevel::Normal)
-                });
-                self.typed = true;
-                if self.state.exiting {std::process::exit(0x0)}
-            }
         } else {
             egui::CentralPanel::default().show(ctx, |ui| {
+                // the display is split around the cursor, laid out from the right
+                let (before, after) = text.split_at(text.len() - self.state.equation.chars_after_cursor());
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    let after = ui.add(Label::new(egui::RichText::new(after).size(size)).wrap(false));
+                    ui.add_space(-8.5);
                     ui.label(cursor);
                     ui.add_space(-8.5);
-                    let resp = ui.add(Label::new(display).wrap(false));
+                    let resp = ui.add(Label::new(egui::RichText::new(before).size(size)).wrap(false)).union(after);
                     if self.typed {
                         self.fit_text(ctx, resp);
                         self.typed = false;
                     };
                 });
             });
-            if ctx.input(|i| i.key_down(egui::Key::Enter)) {
-                self.state.equation.eval_mut();
-                self.state.cached_equation_display = None;
-                self.typed = true;
-            }
+        }
+
+        if let Some(rect) = rect {
+            self.draw_memory(ctx, rect);
+            self.draw_base(ctx, rect);
+            self.draw_listing(ctx, rect);
+        }
+
+        if let Some(message) = self.state.message.take() {
+            self.alert(message, self.config.vars_alert_time);
         }
 
         if self.alert_timer > 0. {
             self.alert_timer -= ctx.input(|i| i.unstable_dt);
-            if rect.is_some() { self.draw_alert(ctx, rect.unwrap()) }
+            if let Some(rect) = rect { self.draw_alert(ctx, rect) }
         }
         ctx.request_repaint_after(Duration::from_millis(500));
     }
 }
+
+impl eframe::App for AppState {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.show(ctx);
+    }
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.state.before_exit();
+    }
+}
+
+fn chord(key: egui::Key, modifiers: egui::Modifiers) -> Option<Chord> {
+    let ctrl = modifiers.ctrl || modifiers.mac_cmd;
+    let key = match key {
+        egui::Key::Enter => Key::Enter,
+        egui::Key::Backspace => Key::Backspace,
+        egui::Key::Delete => Key::Delete,
+        egui::Key::Escape => Key::Esc,
+        egui::Key::Tab => Key::Tab,
+        egui::Key::Space => Key::Space,
+        egui::Key::ArrowUp => Key::Up,
+        egui::Key::ArrowDown => Key::Down,
+        egui::Key::ArrowLeft => Key::Left,
+        egui::Key::ArrowRight => Key::Right,
+        egui::Key::Home => Key::Home,
+        egui::Key::End => Key::End,
+        // characters without modifiers come in as text
+        key if ctrl || modifiers.alt => {
+            let mut chars = key.symbol_or_name().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c),
+                _ => return None,
+            }
+        },
+        _ => return None,
+    };
+    Some(Chord::new(key, ctrl, modifiers.alt, modifiers.shift))
+}
+
+#[test]
+fn startup_errors_test() {
+    let mut state = minicalc::State::with_config(crate::config::Config::default());
+    // reading the vars file always leaves a message
+    state.message = Some("no file 'minicalc-vars'".to_owned());
+    let mut app = AppState::new(state, EguiConfig::default(), vec!["minicalc.cfg:3:5: base: bad value".to_owned()]);
+    let ctx = egui::Context::default();
+    _ = ctx.run(egui::RawInput::default(), |ctx| app.show(ctx));
+    assert_eq!(app.alert, "minicalc.cfg:3:5: base: bad value\nno file 'minicalc-vars'");
+    assert!(app.alert_timer > app.config.vars_alert_time);
+    // once they time out, alerts replace each other again
+    app.alert_timer = 0.;
+    app.alert("decimal".to_owned(), 1.);
+    assert_eq!(app.alert, "decimal");
+}
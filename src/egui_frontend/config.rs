@@ -1,9 +1,7 @@
 use eframe::epaint::Color32;
-use platform_dirs::AppDirs;
 use serde::{Serialize, Deserialize};
-use std::fs;
-use ron::de::from_reader;
-use ron::ser::{to_string_pretty, PrettyConfig};
+use std::path::{Path, PathBuf};
+use crate::config::{config_dir, read_file, save_file, ConfigError};
 
 #[derive(Clone)]
 pub struct ActualColors {
@@ -108,27 +106,55 @@ impl EguiConfig {
         try_color!(self, alert_bg_color);
         self.clone()
     }
-    pub fn load() -> Self {
-        let dirs = AppDirs::new(Some("minicalc"), false).unwrap();
-        let config_dir = dirs.config_dir;
-        let _ = fs::create_dir_all(&config_dir);
-        let egui_config_path = config_dir.join("egui.cfg");
-        let file = fs::File::open(&egui_config_path);
-        let file = match file {
-            Ok(file) => {file},
-            Err(_) => {
-                _ = fs::write(egui_config_path, to_string_pretty(&Self::default(), PrettyConfig::default()).unwrap());
-                return Self::default().with_try_parse_colors()
-            }
-        };
-        let conf = from_reader::<fs::File, Self>(file);
-        match conf {
-            Ok(conf) => {
+    pub fn path() -> PathBuf {
+        config_dir().join("egui.cfg")
+    }
+    // the config file as it is now, without writing back defaults
+    pub fn read() -> Result<Self, Vec<ConfigError>> {
+        let path = Self::path();
+        match read_file::<Self>(&path) {
+            Ok(Some((mut conf, source))) => {
+                let errors = conf.file_errors(&path, &source);
+                if errors.is_empty() {Ok(conf.with_try_parse_colors())} else {Err(errors)}
+            },
+            Ok(None) => Err(vec![ConfigError {path, position: None, field: None, message: "no such file".to_owned()}]),
+            Err(e) => Err(vec![e]),
+        }
+    }
+    // the config, with defaults for a missing file or bad values and the errors that made it fall back to them
+    pub fn load() -> (Self, Vec<ConfigError>) {
+        let path = Self::path();
+        match read_file::<Self>(&path) {
+            Ok(Some((mut conf, source))) => {
                 // write back default values of any fields not present
-                _ = fs::write(egui_config_path, to_string_pretty(&conf, PrettyConfig::default()).unwrap());
-                conf
+                _ = conf.save();
+                let errors = conf.file_errors(&path, &source);
+                (conf.with_try_parse_colors(), errors)
+            },
+            Ok(None) => {
+                _ = Self::default().save();
+                (Self::default().with_try_parse_colors(), vec![])
             },
-            Err(_) => Self::default(),
-        }.with_try_parse_colors()
+            Err(e) => (Self::default().with_try_parse_colors(), vec![e]),
+        }
+    }
+    pub fn file_errors(&self, path: &Path, source: &str) -> Vec<ConfigError> {
+        self.invalid_fields().into_iter().map(|(field, message)| ConfigError::in_field(path, source, field, message)).collect()
+    }
+    // fields with values that parse but can't be used, like a color that isn't hex
+    pub fn invalid_fields(&self) -> Vec<(&'static str, String)> {
+        [("bg_color", &self.bg_color), ("text_color", &self.text_color), ("alert_bg_color", &self.alert_bg_color)].into_iter()
+            .filter(|(_, color)| Color32::from_hex(color).is_err())
+            .map(|(name, color)| (name, format!("'{color}' is not a color like #RRGGBBAA")))
+            .collect()
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        match self.invalid_fields().first() {
+            Some((name, message)) => Err(format!("bad value for {name}: {message}")),
+            None => Ok(()),
+        }
+    }
+    pub fn save(&self) -> Result<(), String> {
+        save_file(&Self::path(), self)
     }
 }
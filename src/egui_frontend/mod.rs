@@ -8,8 +8,12 @@ pub mod config;
 
 pub fn egui_main(args: Args) -> Result<(), eframe::Error> {
     let mut app = AppState::default();
-    app.state.vars_path = args.vars;
-    app.read_vars();
+    app.state.vars_path = args.vars_path();
+    app.state.read_vars();
+    app.state.restore_session();
+    if let Some(name) = &args.workspace {
+        app.state.open_workspace(name);
+    }
 
     let mut viewport = egui::ViewportBuilder::default()
         .with_decorations(app.window_decorated)
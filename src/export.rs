@@ -0,0 +1,92 @@
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    #[value(alias = "markdown")]
+    Md,
+}
+
+// a variable or an evaluated equation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportEntry {
+    pub kind: &'static str,// `variable` or `calculation`
+    pub expression: String,// the variable name or the equation
+    pub exact: String,// the value as an exact fraction like `1/3`
+    pub value: String,// the value as displayed
+}
+
+const COLUMNS: [&str; 4] = ["kind", "expression", "exact", "value"];
+
+impl ExportEntry {
+    fn fields(&self) -> [&str; 4] {
+        [self.kind, &self.expression, &self.exact, &self.value]
+    }
+}
+
+pub fn export(entries: &[ExportEntry], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => {
+            let mut out = COLUMNS.join(",") + "\n";
+            for entry in entries {
+                out += &entry.fields().map(csv_field).join(",");
+                out += "\n";
+            }
+            out
+        },
+        ExportFormat::Json => {
+            let objects: Vec<String> = entries.iter().map(|entry| {
+                let fields: Vec<String> = COLUMNS.iter().zip(entry.fields())
+                    .map(|(column, field)| format!("\"{column}\": {}", json_string(field)))
+                    .collect();
+                format!("  {{{}}}", fields.join(", "))
+            }).collect();
+            if objects.is_empty() {"[]\n".to_owned()} else {format!("[\n{}\n]\n", objects.join(",\n"))}
+        },
+        ExportFormat::Md => {
+            let mut out = format!("| {} |\n|{}\n", COLUMNS.join(" | "), "---|".repeat(COLUMNS.len()));
+            for entry in entries {
+                out += &format!("| {} |\n", entry.fields().map(|field| field.replace('|', "\\|")).join(" | "));
+            }
+            out
+        },
+    }
+}
+
+// quoted if it has a separator, quote or line break in it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = "\"".to_owned();
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+#[test]
+fn export_test() {
+    let entries = [
+        ExportEntry {kind: "variable", expression: "third".to_owned(), exact: "1/3".to_owned(), value: "0.3333".to_owned()},
+        ExportEntry {kind: "calculation", expression: "1, \"2\" | 3".to_owned(), exact: "5".to_owned(), value: "5".to_owned()},
+    ];
+    assert_eq!(export(&entries, ExportFormat::Csv), "kind,expression,exact,value\nvariable,third,1/3,0.3333\ncalculation,\"1, \"\"2\"\" | 3\",5,5\n");
+    assert_eq!(export(&entries[1..], ExportFormat::Json), "[\n  {\"kind\": \"calculation\", \"expression\": \"1, \\\"2\\\" | 3\", \"exact\": \"5\", \"value\": \"5\"}\n]\n");
+    assert_eq!(export(&entries[1..], ExportFormat::Md), "| kind | expression | exact | value |\n|---|---|---|---|\n| calculation | 1, \"2\" \\| 3 | 5 | 5 |\n");
+    assert_eq!(<ExportFormat as ValueEnum>::from_str("markdown", true), Ok(ExportFormat::Md));
+}
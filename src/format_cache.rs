@@ -0,0 +1,130 @@
+use crate::math::{Num, NumComponent, base::NumberBase, parsefmt::fmt};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// numerator and denominator rather than the Num itself, hashing a Ratio walks its whole
+// continued fraction and overflows the stack for long operands
+type Key = (NumComponent, NumComponent, NumberBase, u32);
+
+// formats at least this big are done on the worker thread
+const EXPENSIVE_PLACES: u32 = 4096;
+const EXPENSIVE_BITS: u64 = 65536;
+// fractional places shown while the full format is computed
+const PREVIEW_PLACES: u32 = 64;
+// how many formatted numbers to keep before starting over
+const MAX_ENTRIES: usize = 256;
+
+// formatted numbers by (value, base, precision), expensive ones are computed on a worker thread
+pub struct FormatCache {
+    entries: HashMap<Key, String>,
+    pending: HashSet<Key>,
+    jobs: Sender<Key>,
+    done: Receiver<(Key, Option<String>)>,
+}
+
+impl Default for FormatCache {
+    fn default() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Key>();
+        let (done_sender, done) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(mut key) = job_receiver.recv() {
+                // only the newest request matters while typing, the rest are given back unformatted
+                while let Ok(newer) = job_receiver.try_recv() {
+                    if done_sender.send((key, None)).is_err() {return};
+                    key = newer;
+                }
+                let n = Num::new_raw(key.0.clone(), key.1.clone());
+                let formatted = fmt(n, key.2.clone(), key.3);
+                if done_sender.send((key, Some(formatted))).is_err() {return};
+            }
+        });
+        Self {entries: HashMap::new(), pending: HashSet::new(), jobs, done}
+    }
+}
+
+impl FormatCache {
+    // `n` formatted, or a truncated preview ending in `…` while the worker formats it
+    pub fn get(&mut self, n: &Num, base: &NumberBase, max_fract_places: u32) -> String {
+        let key = (n.numer().clone(), n.denom().clone(), base.clone(), max_fract_places);
+        if let Some(formatted) = self.entries.get(&key) {
+            return formatted.clone();
+        }
+        let bits = n.numer().bits() + n.denom().bits();
+        // integers have no fractional places to format, however many are allowed
+        if (max_fract_places < EXPENSIVE_PLACES || n.is_integer()) && bits < EXPENSIVE_BITS {
+            let formatted = fmt(n.clone(), base.clone(), max_fract_places);
+            self.insert(key, formatted.clone());
+            return formatted;
+        }
+        if !self.pending.contains(&key) && self.jobs.send(key.clone()).is_ok() {
+            self.pending.insert(key);
+        }
+        if n.to_integer().bits() < EXPENSIVE_BITS {
+            fmt(n.clone(), base.clone(), PREVIEW_PLACES.min(max_fract_places)) + "…"
+        } else {
+            "…".to_owned()
+        }
+    }
+
+    // collects formats finished by the worker, true if there were any
+    pub fn poll(&mut self) -> bool {
+        let mut finished = false;
+        while let Ok((key, formatted)) = self.done.try_recv() {
+            self.pending.remove(&key);
+            if let Some(formatted) = formatted {
+                self.insert(key, formatted);
+                finished = true;
+            }
+        }
+        finished
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn insert(&mut self, key: Key, formatted: String) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.clear();
+        }
+        self.entries.insert(key, formatted);
+    }
+}
+
+#[test]
+fn format_cache_test() {
+    use crate::math::NumComponent;
+    let mut cache = FormatCache::default();
+    let third = Num::new(NumComponent::from(1), NumComponent::from(3));
+    assert_eq!(cache.get(&third, &NumberBase::Decimal, 4), "0.3333");
+
+    let preview = cache.get(&third, &NumberBase::Decimal, 10_000);
+    assert_eq!(preview, format!("0.{}…", "3".repeat(64)));
+    while cache.is_pending() {
+        cache.poll();
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(cache.get(&third, &NumberBase::Decimal, 10_000), format!("0.{}", "3".repeat(10_000)));
+    assert_eq!(cache.get(&Num::from(NumComponent::from(8)), &NumberBase::Decimal, 10_000), "8");
+    assert!(!cache.is_pending());
+}
+
+#[test]
+fn format_cache_long_continued_fraction_test() {
+    use crate::math::NumComponent;
+    // consecutive fibonacci numbers have a continued fraction as long as the sequence
+    let (mut a, mut b) = (NumComponent::from(1), NumComponent::from(1));
+    for _ in 0..60_000 {
+        let next = &a + &b;
+        a = std::mem::replace(&mut b, next);
+    }
+    let golden = Num::new(b, a);
+    let mut cache = FormatCache::default();
+    assert_eq!(cache.get(&golden, &NumberBase::Decimal, 4), "1.6180…");
+    while cache.is_pending() {
+        cache.poll();
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(cache.get(&golden, &NumberBase::Decimal, 4), "1.6180");
+}
@@ -0,0 +1,185 @@
+use clap::ValueEnum;
+use std::collections::HashMap;
+use crate::math::{Num, base::NumberBase, parsefmt::parse_in_base};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Csv,// `name,value` rows
+    List,// one value per line, named `v1` to `vN`
+    Env,// `KEY=VALUE` lines
+}
+
+impl ImportFormat {
+    // env if any line assigns, csv if any has a comma, a list otherwise
+    pub fn detect(text: &str) -> Self {
+        let lines = || text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        if lines().any(|line| line.contains('=')) {
+            Self::Env
+        } else if lines().any(|line| line.contains(',')) {
+            Self::Csv
+        } else {
+            Self::List
+        }
+    }
+}
+
+// the variables read from a file and the lines that couldn't be used, by line number
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Import {
+    pub variables: Vec<(String, Num)>,
+    pub skipped: Vec<(usize, String)>,
+}
+
+impl Import {
+    // a one line summary like `imported 3 variables, skipped 1 line`
+    pub fn summary(&self) -> String {
+        let plural = |n: usize, word: &str| if n == 1 {format!("{n} {word}")} else {format!("{n} {word}s")};
+        let mut summary = format!("imported {}", plural(self.variables.len(), "variable"));
+        if !self.skipped.is_empty() {
+            summary += &format!(", skipped {}", plural(self.skipped.len(), "line"));
+        }
+        summary
+    }
+
+    // `line 3: ...` for every skipped line
+    pub fn skipped_lines(&self) -> Vec<String> {
+        self.skipped.iter().map(|(line, reason)| format!("line {line}: {reason}")).collect()
+    }
+}
+
+// names like the ones typed in equations, `rate` or `_total2`
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_alphabetic()) && chars.all(|c| c == '_' || c.is_alphanumeric())
+}
+
+// the columns of a csv row, quoted ones like `"1,000"` can hold commas and `""` for a quote
+fn csv_columns(line: &str) -> Vec<String> {
+    let mut columns = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        let column = columns.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                column.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => columns.push(String::new()),
+            c => column.push(c),
+        }
+    }
+    columns.into_iter().map(|column| column.trim().to_owned()).collect()
+}
+
+// `1,000.5` without its thousands separators, None for commas that don't group thousands like `1,5`
+fn without_thousands(value: &str) -> Option<String> {
+    if !value.contains(',') {return Some(value.to_owned())};
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let mut groups = integer.split(',');
+    let first = groups.next()?;
+    let digits = |group: &str| group.chars().all(|c| c.is_ascii_digit());
+    if first.is_empty() || first.len() > 3 || !digits(first) || fraction.contains(',') {return None};
+    if !groups.all(|group| group.len() == 3 && digits(group)) {return None};
+    Some(value.replace(',', ""))
+}
+
+// reads variables from `text`, values without a `0x` or `0b` prefix are read in `base`,
+// list values are named after the `v1` to `vN` in `existing`, names that are `reserved` are skipped
+pub fn import(text: &str, format: ImportFormat, base: &NumberBase, existing: &HashMap<String, Num>, reserved: impl Fn(&str) -> bool) -> Import {
+    let mut import = Import::default();
+    let mut next = 1;// the number of the next list name
+    let mut first_row = true;
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {continue};
+        // the first row of a csv file can be a header like `name,value`
+        let header = format == ImportFormat::Csv && first_row;
+        first_row = false;
+        let (name, value) = match format {
+            ImportFormat::List => {
+                while existing.contains_key(&format!("v{next}")) {next += 1};
+                (format!("v{next}"), line.to_owned())
+            },
+            ImportFormat::Csv => {
+                match csv_columns(line).as_slice() {
+                    // `1,000` is a thousand in a quoted column
+                    [name, value, ..] => match without_thousands(value) {
+                        Some(value) => (name.clone(), value),
+                        None if header => continue,
+                        None => {
+                            import.skipped.push((number, format!("bad value '{value}'")));
+                            continue
+                        },
+                    },
+                    _ => {
+                        import.skipped.push((number, "expected 'name,value'".to_owned()));
+                        continue
+                    },
+                }
+            },
+            ImportFormat::Env => {
+                let line = line.strip_prefix("export ").unwrap_or(line);
+                match line.split_once('=') {
+                    Some((name, value)) => (name.trim().to_owned(), value.trim().trim_matches(['"', '\'']).to_owned()),
+                    None => {
+                        import.skipped.push((number, "expected 'KEY=VALUE'".to_owned()));
+                        continue
+                    },
+                }
+            },
+        };
+        let n = match parse_in_base(&value, base.clone()) {
+            Some(n) => n,
+            None if header => continue,
+            None => {
+                import.skipped.push((number, format!("bad value '{value}'")));
+                continue
+            },
+        };
+        if !is_name(&name) {
+            import.skipped.push((number, format!("bad name '{name}'")));
+        } else if reserved(&name) {
+            import.skipped.push((number, format!("reserved name '{name}'")));
+        } else {
+            import.variables.push((name, n));
+            if format == ImportFormat::List {next += 1};
+        }
+    }
+    import
+}
+
+#[test]
+fn import_test() {
+    use crate::math::NumComponent;
+    let n = |n: i32| Num::from(NumComponent::from(n));
+    let none = HashMap::new();
+    let reserved = |name: &str| name == "ans";
+
+    let text = "name,value\nrate,0x10\nbad name,1\ntotal,12abc\nans,1\nbig,\"1,000\"\nhalf,\"1,5\"\n";
+    assert_eq!(ImportFormat::detect(text), ImportFormat::Csv);
+    let csv = import(text, ImportFormat::Csv, &NumberBase::Decimal, &none, reserved);
+    assert_eq!(csv.variables, vec![("rate".to_owned(), n(16)), ("big".to_owned(), n(1000))]);
+    assert_eq!(csv.skipped_lines(), vec!["line 3: bad name 'bad name'", "line 4: bad value '12abc'", "line 5: reserved name 'ans'", "line 7: bad value '1,5'"]);
+    assert_eq!(csv.summary(), "imported 2 variables, skipped 4 lines");
+    assert_eq!(without_thousands("-12,345,678.25"), Some("-12345678.25".to_owned()));
+    assert_eq!(without_thousands("1234,567"), None);
+    assert_eq!(without_thousands(",123"), None);
+    // a first row with a number is data, not a header
+    assert_eq!(import("bad name,1\n", ImportFormat::Csv, &NumberBase::Decimal, &none, reserved).skipped.len(), 1);
+
+    let text = "# build flags\nexport FLAGS=\"0xff\"\nMASK = 0b11\n";
+    assert_eq!(ImportFormat::detect(text), ImportFormat::Env);
+    let env = import(text, ImportFormat::Env, &NumberBase::Decimal, &none, reserved);
+    assert_eq!(env.variables, vec![("FLAGS".to_owned(), n(255)), ("MASK".to_owned(), n(3))]);
+
+    // list names continue after the ones already there
+    let existing = HashMap::from([("v1".to_owned(), n(7))]);
+    let list = import("1\n\nx\n-2.5\n", ImportFormat::List, &NumberBase::Decimal, &existing, reserved);
+    assert_eq!(list.variables.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["v2", "v3"]);
+    assert_eq!(list.skipped, vec![(3, "bad value 'x'".to_owned())]);
+    assert_eq!(import("ff", ImportFormat::List, &NumberBase::Hexadecimal, &none, reserved).variables, vec![("v1".to_owned(), n(255))]);
+}
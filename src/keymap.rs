@@ -0,0 +1,210 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+// everything a key can be bound to in the `keymap` config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Evaluate,// or execute the command being typed
+    Delete,
+    DeleteForward,
+    ToggleBase,// the next base in the `base_cycle` config
+    BaseBinary,
+    BaseDecimal,
+    BaseHexadecimal,
+    Copy,
+    Paste,
+    Undo,
+    Store,// store the result in memory
+    CommandMode,
+    Cancel,// leave command mode or close a listing
+    Complete,
+    HistoryPrev,
+    HistoryNext,
+    CursorLeft,
+    CursorRight,
+    CursorHome,
+    CursorEnd,
+    MemoryAdd,
+    MemorySub,
+    MemoryRecall,
+    MemoryClear,
+    Unbound,// takes a default binding off a key
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Esc,
+    Tab,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+const NAMED_KEYS: &[(&str, Key)] = &[
+    ("Enter", Key::Enter),
+    ("Backspace", Key::Backspace),
+    ("Delete", Key::Delete),
+    ("Esc", Key::Esc),
+    ("Tab", Key::Tab),
+    ("Space", Key::Space),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Home", Key::Home),
+    ("End", Key::End),
+];
+
+// a key with modifiers, written like `Ctrl+Z`, `Alt+Shift+Left` or `:`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub key: Key,
+}
+
+impl Chord {
+    // builds a chord from what a frontend reports, shift is already part of typed characters
+    pub fn new(key: Key, ctrl: bool, alt: bool, shift: bool) -> Self {
+        let (key, shift) = match key {
+            Key::Char(c) if ctrl || alt => (Key::Char(c.to_ascii_uppercase()), false),
+            Key::Char(c) => (Key::Char(c), false),
+            key => (key, shift),
+        };
+        Self {ctrl, alt, shift, key}
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut ctrl, mut alt, mut shift) = (false, false, false);
+        let mut rest = s;
+        // a lone `+` is a key, not a separator
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" | "cmd" => ctrl = true,
+                "alt" => alt = true,
+                "shift" => shift = true,
+                _ => return Err(format!("unknown modifier '{modifier}' in '{s}'")),
+            }
+            rest = key;
+        }
+        let key = match NAMED_KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(rest)) {
+            Some((_, key)) => *key,
+            None => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Key::Char(c),
+                    _ => return Err(format!("unknown key '{rest}' in '{s}'")),
+                }
+            },
+        };
+        Ok(Self::new(key, ctrl, alt, shift))
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {write!(f, "Ctrl+")?};
+        if self.alt {write!(f, "Alt+")?};
+        if self.shift {write!(f, "Shift+")?};
+        match self.key {
+            Key::Char(c) => write!(f, "{c}"),
+            key => write!(f, "{}", NAMED_KEYS.iter().find(|(_, named)| *named == key).unwrap().0),
+        }
+    }
+}
+
+// the default bindings with the keymap config parsed into chords on top
+pub struct Keymap {
+    bindings: HashMap<Chord, Action>,
+}
+
+impl Keymap {
+    // also returns the errors for chords that couldn't be parsed
+    pub fn new(config: &BTreeMap<String, Action>) -> (Self, Vec<String>) {
+        let mut bindings = HashMap::new();
+        let mut errors = vec![];
+        for (chord, action) in default_keymap().iter().chain(config) {
+            match Chord::from_str(chord) {
+                Ok(chord) if *action == Action::Unbound => {bindings.remove(&chord);},
+                Ok(chord) => {bindings.insert(chord, *action);},
+                Err(e) => errors.push(e),
+            }
+        }
+        (Self {bindings}, errors)
+    }
+
+    pub fn get(&self, chord: &Chord) -> Option<Action> {
+        self.bindings.get(chord).copied()
+    }
+}
+
+pub fn default_keymap() -> BTreeMap<String, Action> {
+    [
+        (":", Action::CommandMode),
+        ("Enter", Action::Evaluate),
+        ("Backspace", Action::Delete),
+        ("Delete", Action::DeleteForward),
+        ("Esc", Action::Cancel),
+        ("Tab", Action::Complete),
+        ("Up", Action::HistoryPrev),
+        ("Down", Action::HistoryNext),
+        ("Left", Action::CursorLeft),
+        ("Right", Action::CursorRight),
+        ("Home", Action::CursorHome),
+        ("End", Action::CursorEnd),
+        ("Ctrl+P", Action::MemoryAdd),
+        ("Ctrl+N", Action::MemorySub),
+        ("Ctrl+R", Action::MemoryRecall),
+        ("Ctrl+L", Action::MemoryClear),
+        ("Ctrl+C", Action::Copy),
+        ("Ctrl+V", Action::Paste),
+        ("Ctrl+Z", Action::Undo),
+        ("Ctrl+S", Action::Store),
+        ("Ctrl+T", Action::ToggleBase),
+        ("Ctrl+B", Action::BaseBinary),
+        ("Ctrl+D", Action::BaseDecimal),
+        ("Alt+H", Action::BaseHexadecimal),// most terminals send Ctrl+H as backspace
+    ].into_iter().map(|(chord, action)| (chord.to_owned(), action)).collect()
+}
+
+#[test]
+fn chord_test() {
+    assert_eq!(Chord::from_str("ctrl+z"), Ok(Chord::new(Key::Char('Z'), true, false, false)));
+    assert_eq!(Chord::from_str("Alt+Shift+left").map(|c| c.to_string()), Ok("Alt+Shift+Left".to_owned()));
+    assert_eq!(Chord::from_str("+"), Ok(Chord::new(Key::Char('+'), false, false, false)));
+    assert_eq!(Chord::from_str("Ctrl++").map(|c| c.to_string()), Ok("Ctrl++".to_owned()));
+    assert!(Chord::from_str("Hyper+Q").is_err());
+    assert!(Chord::from_str("Enterr").is_err());
+
+    let (keymap, errors) = Keymap::new(&default_keymap());
+    assert!(errors.is_empty());
+    // shift is part of a typed character like `:`
+    assert_eq!(keymap.get(&Chord::new(Key::Char(':'), false, false, true)), Some(Action::CommandMode));
+    assert_eq!(keymap.get(&Chord::new(Key::Char('p'), true, false, false)), Some(Action::MemoryAdd));
+    assert_eq!(keymap.get(&Chord::new(Key::Char('h'), false, true, false)), Some(Action::BaseHexadecimal));
+}
+
+#[test]
+fn keymap_config_test() {
+    // the config adds to the default bindings, a key can only lose its binding on purpose
+    let config = [("Ctrl+Y", Action::Undo), ("ctrl+z", Action::Unbound), ("Enter", Action::Evaluate)];
+    let (keymap, errors) = Keymap::new(&config.into_iter().map(|(chord, action)| (chord.to_owned(), action)).collect());
+    assert!(errors.is_empty());
+    assert_eq!(keymap.get(&Chord::new(Key::Char('y'), true, false, false)), Some(Action::Undo));
+    assert_eq!(keymap.get(&Chord::new(Key::Enter, false, false, false)), Some(Action::Evaluate));
+    assert_eq!(keymap.get(&Chord::new(Key::Char('c'), true, false, false)), Some(Action::Copy));
+    assert_eq!(keymap.get(&Chord::new(Key::Char('z'), true, false, false)), None);
+}
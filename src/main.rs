@@ -5,7 +5,14 @@ pub mod term_frontend;
 pub mod math;
 pub mod minicalc;
 pub mod config;
+pub mod command_line;
+pub mod format_cache;
+pub mod keymap;
+pub mod workspace;
+pub mod export;
+pub mod import;
 use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Clone, ValueEnum)]
 pub enum Frontend {
@@ -36,14 +43,94 @@ impl std::str::FromStr for Frontend {
 #[derive(Parser)]
 #[command()]
 pub struct Args {
-    #[arg(short, long, default_value = "minicalc-vars")]
-    vars: String,
+    /// the variables file, `minicalc-vars` in the config directory by default
+    #[arg(short, long)]
+    vars: Option<String>,
+    /// open this workspace from the `workspaces` config directory
+    #[arg(short, long)]
+    workspace: Option<String>,
     #[arg(short, long, default_value = "egui")]
     frontend: Frontend,
+    /// report problems with the config files and exit
+    #[arg(long)]
+    check_config: bool,
+    /// read config files from this directory, overrides MINICALC_CONFIG_DIR
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+    /// keep config files in `minicalc-config` next to the executable, unless MINICALC_CONFIG_DIR is set
+    #[arg(long)]
+    portable: bool,
+    /// print the variables and calculations to stdout and exit
+    #[arg(long, value_name = "FORMAT")]
+    export: Option<export::ExportFormat>,
+    /// the base of the values `--export` prints, the config's or workspace's by default
+    #[arg(long, value_name = "BASE", requires = "export")]
+    export_base: Option<math::base::NumberBase>,
+}
+
+impl Args {
+    pub fn vars_path(&self) -> String {
+        match &self.vars {
+            Some(vars) => vars.clone(),
+            None => config::config_dir().join("minicalc-vars").to_string_lossy().into(),
+        }
+    }
+}
+
+// prints every config error, true if there were none
+fn check_config() -> bool {
+    use egui_frontend::config::EguiConfig;
+    let mut errors = vec![];
+    match config::read_file::<config::Config>(&config::Config::path()) {
+        Ok(Some((conf, source))) => errors.extend(conf.file_errors(&config::Config::path(), &source)),
+        Ok(None) => {},
+        Err(e) => errors.push(e),
+    }
+    match config::read_file::<EguiConfig>(&EguiConfig::path()) {
+        Ok(Some((conf, source))) => errors.extend(conf.file_errors(&EguiConfig::path(), &source)),
+        Ok(None) => {},
+        Err(e) => errors.push(e),
+    }
+    for e in &errors {
+        eprintln!("{e}");
+    }
+    if errors.is_empty() {
+        println!("config ok: {}", config::config_dir().display());
+    }
+    errors.is_empty()
+}
+
+// the variables and workspace the frontends would start with, printed in `format`
+fn export_stdout(args: &Args, format: export::ExportFormat) {
+    let mut state = minicalc::State {
+        vars_path: args.vars_path(),
+        ..Default::default()
+    };
+    state.read_vars();
+    if let Some(name) = &args.workspace {
+        state.open_workspace(name);
+        if state.workspace.is_none() {
+            eprintln!("{}", state.message.unwrap_or_default());
+            std::process::exit(1);
+        }
+    }
+    let base = args.export_base.clone().unwrap_or(state.base.clone());
+    print!("{}", export::export(&state.export_entries(&base), format));
 }
 
 fn main() {
     let args = Args::parse();
+    if args.portable && config::portable_dir().is_none() {
+        eprintln!("can't find the executable's directory for portable mode");
+    }
+    config::set_config_dir(config::choose_config_dir(args.config_dir.clone(), args.portable));
+    if args.check_config {
+        std::process::exit(if check_config() {0} else {1});
+    }
+    if let Some(format) = args.export {
+        export_stdout(&args, format);
+        return;
+    }
     match args.frontend {
         Frontend::Egui => {
             _ = egui_frontend::egui_main(args);
@@ -1,9 +1,13 @@
+use clap::ValueEnum;
 use serde::{Serialize, Deserialize};
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
 pub enum NumberBase {
+    #[value(alias = "d")]
     Decimal,
+    #[value(alias = "b")]
     Binary,
+    #[value(alias = "x", alias = "hex")]
     Hexadecimal,
 }
 
@@ -15,4 +19,21 @@ impl NumberBase {
             NumberBase::Hexadecimal => 16,
         }
     }
+
+    pub fn name(&self) -> &str {
+        match self {
+            NumberBase::Binary => "binary",
+            NumberBase::Decimal => "decimal",
+            NumberBase::Hexadecimal => "hexadecimal",
+        }
+    }
+
+    // short name for the base indicator
+    pub fn badge(&self) -> &str {
+        match self {
+            NumberBase::Binary => "BIN",
+            NumberBase::Decimal => "DEC",
+            NumberBase::Hexadecimal => "HEX",
+        }
+    }
 }
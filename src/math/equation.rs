@@ -1,10 +1,13 @@
 use self::base::NumberBase;
 use self::operation::Operation;
-use super::parsefmt::fmt;
+use super::parsefmt::{fmt, fmt_digits, parse_digits, prefix};
 use super::*;
 use num_traits::Zero;
 use num_traits::ops::checked::*;
-use std::ops::Rem;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
 
 macro_rules! num {
     ($numer:expr, $denom:expr) => {
@@ -12,12 +15,77 @@ macro_rules! num {
     };
 }
 
+// the most fractional digits a buffer started from a value gets, formatting all of a long one on the UI thread would freeze it,
+// the buffer keeps the exact value until a digit is changed
+const SEEDED_FRACT_PLACES: u32 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvalError {
+    Incomplete,// no operation or right operand
+    Undefined,// division by zero, overflow
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArgCount(String, usize),// function name and how many arguments it takes
+    Syntax(String),
+    TooDeep,// user functions calling each other endlessly
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Incomplete => "incomplete equation".fmt(f),
+            EvalError::Undefined => "undefined result".fmt(f),
+            EvalError::UnknownVariable(name) => write!(f, "no variable '{name}'"),
+            EvalError::UnknownFunction(name) => write!(f, "no function '{name}'"),
+            EvalError::WrongArgCount(name, n) => write!(f, "'{name}' takes {n} arguments"),
+            EvalError::Syntax(msg) => write!(f, "syntax error: {msg}"),
+            EvalError::TooDeep => "too many nested function calls".fmt(f),
+        }
+    }
+}
+
+// what typing into and evaluating an equation depends on
+pub struct Context<'a> {
+    pub base: NumberBase,
+    pub max_fract_places: u32,
+    pub percent_mode: bool,// `%` marks the right operand as a percentage and modulo moves to `#`
+    pub chain: bool,// typing an operator after a complete equation evaluates it first, like `2+3+` giving `5 +`
+    pub repeat: bool,// evaluating without an operation applies the last one again, like `=` `=` `=`
+    pub variables: &'a HashMap<String, Num>,
+}
+
+// the digits of the operand being typed, like `1F.8`, parsed into a `Num` when it is committed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigitBuffer {
+    pub text: String,
+    pub cursor: usize,// digits are ascii, so this is both a char and a byte index
+    pub base: NumberBase,
+    #[serde(skip)]
+    pub exact: Option<Num>,// the value the digits were formatted from, like 1/3, until a digit is changed
+}
+
+impl DigitBuffer {
+    pub fn value(&self) -> Result<Num, EvalError> {
+        if let Some(n) = &self.exact {
+            return Ok(n.clone());
+        }
+        parse_digits(&self.text, self.base.clone()).ok_or(EvalError::Syntax(format!("bad number '{}'", self.text)))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Equation {
     pub left: Num,
     pub operation: Option<Operation>,
     pub right: Option<Num>,
-    pub editing_trailing_zeros: Option<u8>,// None = no fractional part
+    pub right_entered: bool,// the right operand was typed, loaded or recalled, even if it is 0
+    // the digits typed for each operand, only parsed into `left` and `right` on evaluation
+    pub left_digits: Option<DigitBuffer>,
+    pub right_digits: Option<DigitBuffer>,
+    pub right_percent: bool,// right operand is a percentage of the left, e.g. `200 + 15%`
+    pub repeat: Option<(Operation, Num, bool)>,// last evaluated operation, right operand and percent flag
+    pub left_var: Option<String>,// typed `$name`, resolved on evaluation
+    pub right_var: Option<String>,
 }
 
 impl Default for Equation {
@@ -26,22 +94,26 @@ impl Default for Equation {
             left: Num::from(NumComponent::from(0)),
             operation: None,
             right: None,
-            editing_trailing_zeros: None,
+            right_entered: false,
+            left_digits: None,
+            right_digits: None,
+            right_percent: false,
+            repeat: None,
+            left_var: None,
+            right_var: None,
         }
     }
 }
 
 impl Equation {
     pub fn display(&self, base: NumberBase, max_fract_places: u32) -> String {
+        self.display_with(|n| fmt(n.clone(), base.clone(), max_fract_places))
+    }
+    // like `display`, with `format` used for operands that aren't being typed
+    pub fn display_with(&self, mut format: impl FnMut(&Num) -> String) -> String {
         let mut out = "".to_owned();
         
-        out += fmt(self.left.clone(), base.clone(), max_fract_places).as_str();
-
-        if self.editing_left() && self.editing_trailing_zeros.is_some() {
-            if self.left.is_integer() {out += "."};
-            let trailing_zeros = self.editing_trailing_zeros.unwrap() as usize;
-            out += format!("{0:<trailing_zeros$}", "").as_str();
-        }
+        out += Self::display_operand(&self.left, &self.left_var, &self.left_digits, &mut format).as_str();
         
         if self.editing_left() {// no operation or right operand
             return out;
@@ -50,86 +122,235 @@ impl Equation {
         let operation = self.operation.as_ref().unwrap();
         out += format!(" {} ", operation.char()).as_str();
 
-        out += fmt(self.right.as_ref().unwrap().clone(), base, max_fract_places).as_str();
+        out += Self::display_operand(self.right.as_ref().unwrap(), &self.right_var, &self.right_digits, &mut format).as_str();
 
-        if self.editing_left() && self.editing_trailing_zeros.is_some() {
-            if self.right.as_ref().unwrap().clone().is_integer() {out += "."};
-            let trailing_zeros = self.editing_trailing_zeros.unwrap() as usize;
-            out += format!("{0:<trailing_zeros$}", "").as_str();
-        }
+        if self.right_percent {out += "%"};
         
         out
     }
+    fn display_operand(n: &Num, var: &Option<String>, digits: &Option<DigitBuffer>, format: &mut impl FnMut(&Num) -> String) -> String {
+        if let Some(name) = var {
+            return format!("${name}");
+        }
+        match digits {
+            Some(buffer) => {
+                let digits = if buffer.text.is_empty() {"0"} else {buffer.text.as_str()};
+                match digits.strip_prefix('-') {
+                    Some(digits) => format!("-{}{digits}", prefix(&buffer.base)),
+                    None => format!("{}{digits}", prefix(&buffer.base)),
+                }
+            },
+            None => format(n),
+        }
+    }
+    // how many characters of `display` come after the cursor
+    pub fn chars_after_cursor(&self) -> usize {
+        let digits = if self.editing_left() {&self.left_digits} else {&self.right_digits};
+        let buffer = match digits {
+            Some(buffer) => buffer,
+            None => return 0,
+        };
+        let after = buffer.text.len() - buffer.cursor;
+        if self.right_percent {after + 1} else {after}
+    }
     pub fn editing_left(&self) -> bool {
         self.right.is_none()
     }
-    pub fn eval(&self) -> Option<Self> {
-        if self.operation.is_none() || self.operation.is_none() {return None}
+    // the equation with `$name` operands replaced by their values
+    pub fn resolve(&self, variables: &HashMap<String, Num>) -> Result<Self, EvalError> {
+        let lookup = |name: &String| variables.get(name).cloned().ok_or(EvalError::UnknownVariable(name.clone()));
+        let mut resolved = self.clone();
+        resolved.commit()?;
+        if let Some(name) = &self.left_var {
+            resolved.left = lookup(name)?;
+            resolved.left_var = None;
+        }
+        if let Some(name) = &self.right_var {
+            resolved.right = Some(lookup(name)?);
+            resolved.right_var = None;
+        }
+        Ok(resolved)
+    }
 
-        let result: Num = match self.operation.as_ref().unwrap() {
-            Operation::Add => {
-                let r = self.left.checked_add(&self.right.clone().unwrap());
-                match r {
-                    Some(n) => {n},
-                    None => {return None},
-                }
-            },
-            Operation::Sub => {
-                let r = self.left.checked_sub(&self.right.clone().unwrap());
-                match r {
-                    Some(n) => {n},
-                    None => {return None},
-                }
-            },
-            Operation::Mul => {
-                let r = self.left.checked_mul(&self.right.clone().unwrap());
-                match r {
-                    Some(n) => {n},
-                    None => {return None},
-                }
-            },
-            Operation::Div => {
-                let r = self.left.checked_div(&self.right.clone().unwrap());
-                match r {
-                    Some(n) => {n},
-                    None => {return None}
-                }
-            },
-            Operation::Mod => {
-                if self.right.clone().unwrap().is_zero() {return None};
-                self.left.clone().rem(self.right.clone().unwrap())
-            },
-        };
+    pub fn eval(&self, variables: &HashMap<String, Num>) -> Result<Self, EvalError> {
+        if self.operation.is_none() || self.right.is_none() {return Err(EvalError::Incomplete)}
+
+        let resolved = self.resolve(variables)?;
+        let operation = resolved.operation.unwrap();
+        let right = resolved.right.unwrap();
+        let result = Self::apply(&resolved.left, operation, &right, self.right_percent).ok_or(EvalError::Undefined)?;
+
+        Ok(Self {left: result, repeat: Some((operation, right, self.right_percent)), ..Default::default()})
+    }
+
+    fn apply(left: &Num, operation: Operation, right: &Num, right_percent: bool) -> Option<Num> {
+        if right_percent {
+            let fraction = right.checked_div(&num!(100, 1))?;
+            return match operation {
+                Operation::Add => left.checked_add(&left.checked_mul(&fraction)?),
+                Operation::Sub => left.checked_sub(&left.checked_mul(&fraction)?),
+                Operation::Mul => left.checked_mul(&fraction),
+                Operation::Div => left.checked_div(&fraction),
+                // remainders of a percentage make little sense, use the plain fraction
+                op => op.apply(left, &fraction),
+            };
+        }
+        operation.apply(left, right)
+    }
+
+    // multiply the operand being edited by (100 + percent)%, a negative percent gives a discount
+    pub fn apply_markup(&mut self, percent: &Num, variables: &HashMap<String, Num>) -> Result<(), EvalError> {
+        let factor = num!(1, 1).checked_add(&percent.checked_div(&num!(100, 1)).ok_or(EvalError::Undefined)?).ok_or(EvalError::Undefined)?;
+        let n = self.resolve(variables)?.editing_num().checked_mul(&factor).ok_or(EvalError::Undefined)?;
+        self.set_editing_num(n);
+        Ok(())
+    }
+
+    // how many percent the right operand is above or below the left one
+    pub fn percent_change(&self, variables: &HashMap<String, Num>) -> Result<Num, EvalError> {
+        let resolved = self.resolve(variables)?;
+        let right = resolved.right.as_ref().ok_or(EvalError::Incomplete)?;
+        let change = || right.checked_sub(&resolved.left)?.checked_div(&resolved.left)?.checked_mul(&num!(100, 1));
+        change().ok_or(EvalError::Undefined)
+    }
+
+    // parses the typed digits into the operands, a buffer that doesn't parse is kept
+    pub fn commit(&mut self) -> Result<(), EvalError> {
+        if let Some(buffer) = &self.left_digits {
+            self.left = buffer.value()?;
+            self.left_digits = None;
+        }
+        if let Some(buffer) = &self.right_digits {
+            self.right = Some(buffer.value()?);
+            self.right_digits = None;
+        }
+        Ok(())
+    }
 
-        Some(Self {left: result, operation: None, right: None, editing_trailing_zeros: None})
+    // the typed digits of the operand being edited
+    fn digits_mut(&mut self) -> &mut Option<DigitBuffer> {
+        if self.editing_left() {&mut self.left_digits} else {&mut self.right_digits}
     }
 
     fn add_operation(&mut self, op: Operation) {
-        self.editing_trailing_zeros = None;
         self.operation = Some(op);
         self.right = Some(num!(0,1));
+        self.right_entered = false;
     }
 
-    pub fn try_type_single(&mut self, input: &str, base: NumberBase, max_fract_places: u32) {
+    // types one key, returning the result when it evaluates a chained equation like `2+3+`
+    pub fn try_type_single(&mut self, input: &str, ctx: &Context) -> Result<Option<Num>, EvalError> {
+        if let Some(name) = self.editing_var_mut() {
+            if input.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                *name += input;
+                return Ok(None)
+            }
+        }
+        let input = input.to_uppercase();
+        let input = match input.as_str() {
+            "%" if ctx.percent_mode => {
+                if !self.editing_left() {self.right_percent = true};
+                return Ok(None)
+            },
+            "#" if ctx.percent_mode => "%",
+            input => input,
+        };
         match input {
             "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" | "A" | "B" | "C" | "D" | "E" | "F" => {
-                self.try_add_digit(input, base, max_fract_places)
+                let digit = u32::from_str_radix(input, 16).unwrap();
+                if digit >= ctx.base.place_value() {return Ok(None)};
+                self.insert(input, ctx);
+            },
+            "$" => {
+                self.set_editing_num(num!(0, 1));
+                *self.editing_var_mut() = Some("".to_owned());
             },
             "+" | "-" | "*" | "/" | "%" => {
+                let op = Operation::from_str(input).unwrap();
                 if self.editing_left() {
-                    self.add_operation(Operation::from_str(input).unwrap())
+                    self.add_operation(op)
+                } else if self.right_untouched() && !self.right_percent {
+                    if self.operation.unwrap().char().starts_with(op.char()) {
+                        // typing the operator again before the right operand, e.g. `//`
+                        if let Some(retyped) = self.operation.unwrap().retyped() {
+                            self.operation = Some(retyped);
+                        }
+                    } else if ctx.chain {
+                        self.operation = Some(op);
+                    }
+                } else if ctx.chain {
+                    *self = self.eval(ctx.variables)?;
+                    self.add_operation(op);
+                    return Ok(Some(self.left.clone()));
                 }
             },
             "." => {
-                if self.editing_trailing_zeros == None {
-                    self.editing_trailing_zeros = Some(0)
-                }
+                if self.start_buffer(ctx).text.contains('.') {return Ok(None)};
+                self.insert(".", ctx);
             },
             _ => {},
         }
+        Ok(None)
+    }
+
+    // the digit buffer of the operand being edited, started from its value if there isn't one yet
+    fn start_buffer(&mut self, ctx: &Context) -> &mut DigitBuffer {
+        if self.digits_mut().as_ref().is_some_and(|buffer| buffer.base != ctx.base) && self.commit().is_err() {
+            *self.digits_mut() = None;
+        }
+        if self.digits_mut().is_none() {
+            let n = self.editing_num();
+            let places = ctx.max_fract_places.min(SEEDED_FRACT_PLACES);
+            let text = if n.is_zero() {"".to_owned()} else {fmt_digits(n.clone(), ctx.base.clone(), places)};
+            *self.digits_mut() = Some(DigitBuffer {cursor: text.len(), text, base: ctx.base.clone(), exact: Some(n)});
+        }
+        self.digits_mut().as_mut().unwrap()
+    }
+
+    fn insert(&mut self, input: &str, ctx: &Context) {
+        if self.editing_var_mut().is_some() {return};
+        let buffer = self.start_buffer(ctx);
+        // too many fractional digits, like past the end of 1/3
+        let fract_places = buffer.text.split_once('.').map(|(_, fract)| fract.len()).unwrap_or(0);
+        if input != "." && buffer.text[..buffer.cursor].contains('.') && fract_places >= ctx.max_fract_places as usize {return};
+        // nothing goes before the sign
+        if buffer.cursor == 0 && buffer.text.starts_with('-') {return};
+        buffer.text.insert_str(buffer.cursor, input);
+        buffer.cursor += input.len();
+        buffer.exact = None;
     }
 
-    fn editing_num(&self) -> Num {
+    // moves the cursor within the operand being edited, `to` is clamped to its digits
+    pub fn move_cursor(&mut self, to: impl FnOnce(usize, usize) -> usize, ctx: &Context) {
+        if self.editing_var_mut().is_some() {return};
+        let buffer = self.start_buffer(ctx);
+        buffer.cursor = to(buffer.cursor, buffer.text.len()).min(buffer.text.len());
+    }
+
+    // deletes the digit after the cursor
+    pub fn delete_forward(&mut self, ctx: &Context) {
+        if self.editing_var_mut().is_some() {return};
+        let buffer = self.start_buffer(ctx);
+        if buffer.cursor < buffer.text.len() {
+            buffer.text.remove(buffer.cursor);
+            buffer.exact = None;
+        }
+    }
+
+    // an operation was typed but nothing for the right operand yet, like `5*`
+    pub fn right_untouched(&self) -> bool {
+        !self.editing_left() && !self.right_entered && self.right_digits.is_none() && self.right_var.is_none()
+    }
+
+    fn editing_var_mut(&mut self) -> &mut Option<String> {
+        if self.editing_left() {
+            &mut self.left_var
+        } else {
+            &mut self.right_var
+        }
+    }
+
+    pub fn editing_num(&self) -> Num {
         if let Some(n) = self.right.clone() {
             n
         } else {
@@ -137,141 +358,299 @@ impl Equation {
         }
     }
 
-    fn set_editing_num(&mut self, n: Num) {
-        if let Some(_) = self.right {
-            self.right = Some(n)
+    pub fn set_editing_num(&mut self, n: Num) {
+        *self.digits_mut() = None;
+        *self.editing_var_mut() = None;
+        if self.right.is_some() {
+            self.right = Some(n);
+            self.right_entered = true;
         } else {
             self.left = n
         }
     }
 
-    fn add_trailing_zeros(&mut self, n: u8) {
-        let z = self.editing_trailing_zeros;
-        if z == None {
-            self.editing_trailing_zeros = Some(n);
+    pub fn set_left(&mut self, n: Num) {
+        if self.editing_left() {
+            self.set_editing_num(n);
         } else {
-            self.editing_trailing_zeros = Some(z.unwrap().saturating_add(n));
+            self.left = n;
+            self.left_var = None;
+            self.left_digits = None;
         }
     }
 
-    fn try_add_digit(&mut self, input: &str, base: NumberBase, max_fract_places: u32) {
-        let places = "0123456789ABCDEF";
-        let place = places.find(input);
-        if place.is_none() {return;}
-        let digit = place.unwrap();
-        if digit >= base.place_value() as usize {return;}
-        
-        let mut n = self.editing_num();
-        let placevalue = num!(base.place_value(), 1);
-
-        // adding an integer digit
-        if n.is_integer() && self.editing_trailing_zeros == None {
-            let r = n.checked_mul(&placevalue);
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            };
-            let r = n.checked_add(&num!(digit, 1));
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            };
+    pub fn set_right(&mut self, n: Num) {
+        if !self.editing_left() {
             self.set_editing_num(n);
-            return;
-        }
-        // adding a trailing zero
-        if digit == 0 {
-            self.add_trailing_zeros(1);
-            return;
-        }
-        // adding a nonzero fractional digit
-        let mut placesmoved = 0;
-        while !n.is_integer() && placesmoved < max_fract_places {
-            let r = n.checked_mul(&placevalue);
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            };
-            placesmoved += 1;
         }
+    }
 
-        if placesmoved >= max_fract_places {return};// too many digits, maybe even infinite, like 1/3.
-
-        for _ in 0..(self.editing_trailing_zeros.unwrap_or(0) + 1) {
-            let r = n.checked_mul(&placevalue);
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            };
-            placesmoved += 1;
-        }
-        let r = n.checked_add(&num!(digit, 1));
-        n = match r {
-            Some(n) => {n},
-            None => {return},
+    pub fn eval_mut(&mut self, ctx: &Context) -> Result<(), EvalError> {
+        self.commit()?;
+        let result = match (&self.operation, &self.repeat) {
+            (None, Some((operation, right, right_percent))) if ctx.repeat => {
+                let left = self.resolve(ctx.variables)?.left;
+                let left = Self::apply(&left, *operation, right, *right_percent).ok_or(EvalError::Undefined)?;
+                Self {left, repeat: self.repeat.clone(), ..Default::default()}
+            },
+            _ => self.eval(ctx.variables)?,
         };
-        for _ in 0..placesmoved {
-            let r = n.checked_div(&placevalue);
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            };
-        }
-        self.set_editing_num(n);
-        self.editing_trailing_zeros = Some(0);
+        let _ = std::mem::replace(self, result); // thanks borrow checker
+        Ok(())
     }
-    pub fn eval_mut(&mut self) {
-        let result = self.eval();
-        if let Some(result) = result {
-            let _ = std::mem::replace(self, result); // thanks borrow checker
+    // deletes the digit before the cursor, or the operation once the right operand is empty
+    pub fn delete_one_mut(&mut self, ctx: &Context) {
+        if self.right_percent {
+            self.right_percent = false;
+            return;
         }
-    }
-    pub fn delete_one_mut(&mut self, base: NumberBase, max_fract_places: u32) {
-        if self.editing_trailing_zeros.is_some() {
-            let value = self.editing_trailing_zeros.unwrap().clone();
-            let n = self.editing_num();
-            if value > 0 {
-                self.editing_trailing_zeros = Some(value - 1);
-            } else {
-                self.editing_trailing_zeros = None;
+        if let Some(name) = self.editing_var_mut() {
+            if name.pop().is_none() {
+                *self.editing_var_mut() = None;
             }
-            if !n.is_integer() && value == 1 {self.editing_trailing_zeros = None}
             return;
         }
-
-        let placevalue = num!(base.place_value(), 1);
-        let mut n = self.editing_num();
-
-        if !self.editing_left() && n.is_zero() {
+        let right_empty = self.right_digits.as_ref().map(|buffer| buffer.text.is_empty()).unwrap_or(self.right_untouched());
+        if !self.editing_left() && right_empty {
+            self.right_digits = None;
             self.right = None;
             self.operation = None;
+            return;
         }
-
-        let mut times_shifted: i32 = -1;
-        while !n.is_integer() && times_shifted < max_fract_places as i32 {
-            times_shifted += 1;
-            let r = n.checked_mul(&placevalue);
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            }
+        let buffer = self.start_buffer(ctx);
+        if buffer.cursor > 0 {
+            buffer.cursor -= 1;
+            buffer.text.remove(buffer.cursor);
+            buffer.exact = None;
         }
-        n = num!(n.to_integer(), 1);// if theres even more digits (the 128 limit was hit), then chop em off
-        let r = n.checked_div(&placevalue);
-        n = match r {
-            Some(n) => {n},
-            None => {return},
-        };
-        n = n.trunc();
-        while times_shifted > 0 {
-            let r = n.checked_div(&placevalue);
-            n = match r {
-                Some(n) => {n},
-                None => {return},
-            };
-            times_shifted -= 1;
+    }
+}
+
+#[cfg(test)]
+fn test_context(variables: &HashMap<String, Num>, percent_mode: bool, chain: bool) -> Context<'_> {
+    Context {base: NumberBase::Decimal, max_fract_places: 128, percent_mode, chain, repeat: true, variables}
+}
+
+#[cfg(test)]
+fn eval_op(left: i64, op: Operation, right: i64) -> Option<Num> {
+    let equation = Equation {
+        left: num!(left, 1),
+        operation: Some(op),
+        right: Some(num!(right, 1)),
+        right_entered: true,
+        left_digits: None,
+        right_digits: None,
+        right_percent: false,
+        repeat: None,
+        left_var: None,
+        right_var: None,
+    };
+    equation.eval(&HashMap::new()).ok().map(|result| result.left)
+}
+
+#[test]
+fn trunc_div_test() {
+    // truncates toward zero
+    assert_eq!(eval_op(7, Operation::TruncDiv, 2), Some(num!(3, 1)));
+    assert_eq!(eval_op(-7, Operation::TruncDiv, 2), Some(num!(-3, 1)));
+    assert_eq!(eval_op(7, Operation::TruncDiv, -2), Some(num!(-3, 1)));
+    assert_eq!(eval_op(-7, Operation::TruncDiv, -2), Some(num!(3, 1)));
+    assert_eq!(eval_op(7, Operation::TruncDiv, 0), None);
+}
+
+#[test]
+fn mod_test() {
+    // truncating: sign follows the dividend, like C
+    assert_eq!(eval_op(7, Operation::Mod, 3), Some(num!(1, 1)));
+    assert_eq!(eval_op(-7, Operation::Mod, 3), Some(num!(-1, 1)));
+    assert_eq!(eval_op(7, Operation::Mod, -3), Some(num!(1, 1)));
+    assert_eq!(eval_op(-7, Operation::Mod, -3), Some(num!(-1, 1)));
+
+    // floored: sign follows the divisor, like Python
+    assert_eq!(eval_op(7, Operation::FloorMod, 3), Some(num!(1, 1)));
+    assert_eq!(eval_op(-7, Operation::FloorMod, 3), Some(num!(2, 1)));
+    assert_eq!(eval_op(7, Operation::FloorMod, -3), Some(num!(-2, 1)));
+    assert_eq!(eval_op(-7, Operation::FloorMod, -3), Some(num!(-1, 1)));
+
+    // euclidean: never negative
+    assert_eq!(eval_op(7, Operation::EuclidMod, 3), Some(num!(1, 1)));
+    assert_eq!(eval_op(-7, Operation::EuclidMod, 3), Some(num!(2, 1)));
+    assert_eq!(eval_op(7, Operation::EuclidMod, -3), Some(num!(1, 1)));
+    assert_eq!(eval_op(-7, Operation::EuclidMod, -3), Some(num!(2, 1)));
+
+    assert_eq!(eval_op(7, Operation::FloorMod, 0), None);
+    assert_eq!(eval_op(7, Operation::EuclidMod, 0), None);
+}
+
+#[test]
+fn retype_operation_test() {
+    let variables = HashMap::new();
+    let ctx = test_context(&variables, false, false);
+    let mut equation = Equation::default();
+    for key in ["7", "/", "/", "2"] {
+        _ = equation.try_type_single(key, &ctx);
+    }
+    assert_eq!(equation.operation, Some(Operation::TruncDiv));
+
+    let mut equation = Equation::default();
+    for key in ["7", "%", "%", "%"] {
+        _ = equation.try_type_single(key, &ctx);
+    }
+    assert_eq!(equation.operation, Some(Operation::EuclidMod));
+}
+
+#[test]
+fn percent_test() {
+    let variables = HashMap::new();
+    let ctx = test_context(&variables, true, false);
+    let typed = |keys: &str| {
+        let mut equation = Equation::default();
+        for key in keys.chars() {
+            _ = equation.try_type_single(key.to_string().as_str(), &ctx);
         }
+        equation.eval(&variables).ok().map(|result| result.left)
+    };
+    assert_eq!(typed("200+15%"), Some(num!(230, 1)));
+    assert_eq!(typed("200-15%"), Some(num!(170, 1)));
+    assert_eq!(typed("50*20%"), Some(num!(10, 1)));
+    assert_eq!(typed("50/20%"), Some(num!(250, 1)));
+    // modulo moves to `#`
+    assert_eq!(typed("7#3"), Some(num!(1, 1)));
+}
 
-        self.set_editing_num(n);
+#[test]
+fn chain_test() {
+    let variables = HashMap::new();
+    let ctx = test_context(&variables, false, true);
+    let mut equation = Equation::default();
+    for key in "2+3+".chars() {
+        _ = equation.try_type_single(key.to_string().as_str(), &ctx);
+    }
+    assert_eq!(equation.left, num!(5, 1));
+    assert_eq!(equation.operation, Some(Operation::Add));
+
+    // an operator right after another one replaces it
+    _ = equation.try_type_single("*", &ctx);
+    assert_eq!(equation.operation, Some(Operation::Mul));
+
+    _ = equation.try_type_single("2", &ctx);
+    _ = equation.eval_mut(&ctx);
+    assert_eq!(equation.left, num!(10, 1));
+    _ = equation.eval_mut(&ctx);
+    _ = equation.eval_mut(&ctx);
+    assert_eq!(equation.left, num!(40, 1));
+
+    // a right operand of 0 that was put there counts, so the next operator evaluates `x*0`
+    let mut times_zero = Equation::default();
+    for key in "5*".chars() {
+        _ = times_zero.try_type_single(key.to_string().as_str(), &ctx);
+    }
+    times_zero.set_right(num!(0, 1));
+    assert_eq!(times_zero.try_type_single("+", &ctx), Ok(Some(num!(0, 1))));
+    assert_eq!((times_zero.left, times_zero.operation), (num!(0, 1), Some(Operation::Add)));
+
+    // an equation that can't be evaluated stays as it is, with the error for the frontend to show
+    let mut by_zero = Equation::default();
+    for key in "5/0".chars() {
+        _ = by_zero.try_type_single(key.to_string().as_str(), &ctx);
+    }
+    assert_eq!(by_zero.try_type_single("+", &ctx), Err(EvalError::Undefined));
+    assert_eq!((by_zero.display(NumberBase::Decimal, 128), by_zero.operation), ("5 / 0".to_owned(), Some(Operation::Div)));
+
+    // without repeat, evaluating a lone number does nothing
+    let ctx = Context {repeat: false, ..ctx};
+    assert_eq!(equation.eval_mut(&ctx), Err(EvalError::Incomplete));
+    assert_eq!(equation.left, num!(40, 1));
+}
+
+#[test]
+fn variable_test() {
+    let mut variables = HashMap::new();
+    variables.insert("rate".to_owned(), num!(3, 2));
+    let mut equation = Equation::default();
+    for key in "$rate*$x".chars() {
+        _ = equation.try_type_single(key.to_string().as_str(), &test_context(&variables, false, true));
+    }
+    assert_eq!(equation.display(NumberBase::Decimal, 128), "$rate * $x");
+    assert_eq!(equation.eval(&variables), Err(EvalError::UnknownVariable("x".to_owned())));
+
+    // the reference is only resolved on evaluation
+    variables.insert("x".to_owned(), num!(4, 1));
+    assert_eq!(equation.eval(&variables).map(|result| result.left), Ok(num!(6, 1)));
+    variables.insert("rate".to_owned(), num!(2, 1));
+    assert_eq!(equation.eval(&variables).map(|result| result.left), Ok(num!(8, 1)));
+}
+
+#[test]
+fn cursor_test() {
+    let variables = HashMap::new();
+    let ctx = test_context(&variables, false, true);
+    let mut equation = Equation::default();
+    for key in "1234".chars() {
+        _ = equation.try_type_single(key.to_string().as_str(), &ctx);
+    }
+    // fix the second digit: 1234 -> 1534
+    equation.move_cursor(|_, _| 1, &ctx);
+    equation.delete_forward(&ctx);
+    _ = equation.try_type_single("5", &ctx);
+    assert_eq!(equation.display(NumberBase::Decimal, 128), "1534");
+    assert_eq!(equation.chars_after_cursor(), 2);
+    equation.move_cursor(|_, _| 0, &ctx);
+    equation.delete_one_mut(&ctx);// nothing before the cursor
+    _ = equation.try_type_single(".", &ctx);
+    assert_eq!(equation.display(NumberBase::Decimal, 128), ".1534");
+
+    // typed digits are kept as they are until evaluation
+    equation.move_cursor(|_, len| len, &ctx);
+    for key in ["0", "+"] {
+        _ = equation.try_type_single(key, &ctx);
+    }
+    assert_eq!(equation.display(NumberBase::Decimal, 128), ".15340 + 0");
+    // backspace on an empty right operand removes the operation but keeps the left one
+    equation.delete_one_mut(&ctx);
+    assert_eq!(equation.operation, None);
+    assert_eq!(equation.chars_after_cursor(), 0);
+    assert_eq!(equation.resolve(&variables).map(|resolved| resolved.left), Ok(num!(1534, 10000)));
+
+    // moving the cursor through a result keeps it exact, and nothing is typed before its sign
+    let mut equation = Equation {left: num!(-1, 3), ..Default::default()};
+    equation.move_cursor(|_, _| 0, &ctx);
+    _ = equation.try_type_single("5", &ctx);
+    assert_eq!(equation.resolve(&variables).map(|resolved| resolved.left), Ok(num!(-1, 3)));
+
+    // a long result isn't formatted in full to edit it
+    let ctx = Context {max_fract_places: 100_000, ..ctx};
+    let mut equation = Equation {left: num!(1, 3), ..Default::default()};
+    equation.move_cursor(|_, _| 0, &ctx);
+    assert_eq!(equation.left_digits.as_ref().map(|buffer| buffer.text.len()), Some(2 + SEEDED_FRACT_PLACES as usize));
+    assert_eq!(equation.resolve(&variables).map(|resolved| resolved.left), Ok(num!(1, 3)));
+}
+
+// cargo test --release -- --ignored --nocapture keystroke_bench
+#[test]
+#[ignore]
+fn keystroke_bench() {
+    let variables = HashMap::new();
+    let ctx = test_context(&variables, false, true);
+    let mut equation = Equation::default();
+    let start = std::time::Instant::now();
+    for i in 0..1000 {
+        _ = equation.try_type_single(["1", "2", "3", "4", "5", "6", "7", "8", "9"][i % 9], &ctx);
+        equation.display(NumberBase::Decimal, 128);
+    }
+    println!("typing 1000 digits: {:?} per keystroke", start.elapsed() / 1000);
+
+    let start = std::time::Instant::now();
+    for _ in 0..1000 {
+        equation.delete_one_mut(&ctx);
+        equation.display(NumberBase::Decimal, 128);
+    }
+    println!("deleting 1000 digits: {:?} per keystroke", start.elapsed() / 1000);
+
+    for key in std::iter::repeat_n('7', 1000) {
+        _ = equation.try_type_single(key.to_string().as_str(), &ctx);
     }
+    let start = std::time::Instant::now();
+    _ = equation.eval_mut(&ctx);
+    println!("parsing a 1000 digit operand: {:?}", start.elapsed());
 }
@@ -0,0 +1,309 @@
+use super::{Num, parsefmt};
+use super::equation::EvalError;
+use super::operation::Operation;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// how deep user functions may call each other before giving up
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Num(Num),
+    Var(String),
+    Neg(Box<Expr>),
+    Binary(Operation, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+// a user-defined function like `mv(x) = x * 3300 / 4096`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: Expr,
+    pub source: String,// the body as typed, kept for listing and writing back
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Num(Num),
+    Ident(String),
+    Op(Operation),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, EvalError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        i += 1;
+        match c {
+            ' ' | '\t' => {},
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '+' | '-' | '*' | '/' | '%' => {
+                // the longest operator that matches, so `//` isn't two divisions
+                while i < chars.len() && chars[i] == c && Operation::from_str(&chars[start..=i].iter().collect::<String>()).is_ok() {
+                    i += 1;
+                }
+                let op: String = chars[start..i].iter().collect();
+                tokens.push(Token::Op(Operation::from_str(&op).unwrap()));
+            },
+            '0'..='9' | '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let n: String = chars[start..i].iter().collect();
+                let n = parsefmt::parse(n.clone()).ok_or(EvalError::Syntax(format!("bad number '{n}'")))?;
+                tokens.push(Token::Num(n));
+            },
+            // `$` is optional, `$rate` and `rate` are the same variable
+            c if c == '$' || c == '_' || c.is_alphabetic() => {
+                while i < chars.len() && (chars[i] == '_' || chars[i].is_alphanumeric()) {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().filter(|c| **c != '$').collect();
+                if name.is_empty() {return Err(EvalError::Syntax("expected a name after '$'".to_owned()))};
+                tokens.push(Token::Ident(name));
+            },
+            c => return Err(EvalError::Syntax(format!("unexpected '{c}'"))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), EvalError> {
+        if self.next() == Some(token) {
+            Ok(())
+        } else {
+            Err(EvalError::Syntax(format!("expected {what}")))
+        }
+    }
+
+    // sum := term (('+' | '-') term)*
+    fn sum(&mut self) -> Result<Expr, EvalError> {
+        let mut left = self.term()?;
+        while let Some(Token::Op(op @ (Operation::Add | Operation::Sub))) = self.peek().cloned() {
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    // term := unary (('*' | '/' | '//' | '%' | ...) unary)*
+    fn term(&mut self) -> Result<Expr, EvalError> {
+        let mut left = self.unary()?;
+        while let Some(Token::Op(op)) = self.peek().cloned() {
+            if let Operation::Add | Operation::Sub = op {break};
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, EvalError> {
+        match self.peek() {
+            Some(Token::Op(Operation::Sub)) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            },
+            Some(Token::Op(Operation::Add)) => {
+                self.pos += 1;
+                self.unary()
+            },
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, EvalError> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Ident(name)) => {
+                if self.peek() != Some(&Token::Open) {
+                    return Ok(Expr::Var(name));
+                }
+                self.pos += 1;
+                let mut args = vec![];
+                if self.peek() == Some(&Token::Close) {
+                    self.pos += 1;
+                    return Ok(Expr::Call(name, args));
+                }
+                loop {
+                    args.push(self.sum()?);
+                    match self.next() {
+                        Some(Token::Comma) => {},
+                        Some(Token::Close) => break,
+                        _ => return Err(EvalError::Syntax("expected ',' or ')'".to_owned())),
+                    }
+                }
+                Ok(Expr::Call(name, args))
+            },
+            Some(Token::Open) => {
+                let inner = self.sum()?;
+                self.expect(Token::Close, "')'")?;
+                Ok(inner)
+            },
+            Some(_) => Err(EvalError::Syntax("expected a number, name or '('".to_owned())),
+            None => Err(EvalError::Syntax("unexpected end".to_owned())),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = EvalError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {tokens: tokenize(s)?, pos: 0};
+        let expr = parser.sum()?;
+        if parser.peek().is_some() {
+            return Err(EvalError::Syntax("unexpected trailing input".to_owned()));
+        }
+        Ok(expr)
+    }
+}
+
+impl Expr {
+    // a comma separated list like `1 + 2, $rate`, empty for blank input
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, EvalError> {
+        let mut parser = Parser {tokens: tokenize(s)?, pos: 0};
+        let mut exprs = vec![];
+        if parser.peek().is_none() {return Ok(exprs)};
+        loop {
+            exprs.push(parser.sum()?);
+            match parser.next() {
+                Some(Token::Comma) => {},
+                None => return Ok(exprs),
+                _ => return Err(EvalError::Syntax("expected ','".to_owned())),
+            }
+        }
+    }
+
+    pub fn eval(&self, scope: &HashMap<String, Num>, variables: &HashMap<String, Num>, functions: &HashMap<String, Function>) -> Result<Num, EvalError> {
+        self.eval_depth(scope, variables, functions, 0)
+    }
+
+    fn eval_depth(&self, scope: &HashMap<String, Num>, variables: &HashMap<String, Num>, functions: &HashMap<String, Function>, depth: usize) -> Result<Num, EvalError> {
+        let eval = |expr: &Expr| expr.eval_depth(scope, variables, functions, depth);
+        match self {
+            Expr::Num(n) => Ok(n.clone()),
+            Expr::Var(name) => {
+                // parameters shadow variables
+                scope.get(name).or(variables.get(name)).cloned().ok_or(EvalError::UnknownVariable(name.clone()))
+            },
+            Expr::Neg(inner) => Ok(-eval(inner)?),
+            Expr::Binary(op, left, right) => op.apply(&eval(left)?, &eval(right)?).ok_or(EvalError::Undefined),
+            Expr::Call(name, args) => {
+                let args = args.iter().map(eval).collect::<Result<Vec<Num>, EvalError>>()?;
+                let function = functions.get(name).ok_or(EvalError::UnknownFunction(name.clone()))?;
+                function.call_depth(name, &args, variables, functions, depth + 1)
+            },
+        }
+    }
+}
+
+impl Function {
+    // parses a definition like `mv(x) = x * 3300 / 4096` into the name and function
+    pub fn parse_definition(definition: &str) -> Result<(String, Self), EvalError> {
+        let (head, body) = definition.split_once('=').ok_or(EvalError::Syntax("expected '='".to_owned()))?;
+        let (name, params) = head.trim().strip_suffix(')').and_then(|head| head.split_once('('))
+            .ok_or(EvalError::Syntax("expected 'name(params)'".to_owned()))?;
+        let is_name = |s: &str| !s.is_empty() && !s.starts_with(|c: char| c.is_ascii_digit()) && s.chars().all(|c| c == '_' || c.is_alphanumeric());
+        let name = name.trim();
+        if !is_name(name) {return Err(EvalError::Syntax(format!("bad function name '{name}'")))};
+        let params: Vec<String> = params.split(',').map(|p| p.trim().to_owned()).filter(|p| !p.is_empty()).collect();
+        if let Some(param) = params.iter().find(|p| !is_name(p)) {
+            return Err(EvalError::Syntax(format!("bad parameter name '{param}'")));
+        }
+        let source = body.trim().to_owned();
+        let body = Expr::from_str(&source)?;
+        Ok((name.to_owned(), Self {params, body, source}))
+    }
+
+    // the `name(params)` part of the definition
+    pub fn signature(&self, name: &str) -> String {
+        format!("{}({})", name, self.params.join(", "))
+    }
+
+    pub fn call(&self, name: &str, args: &[Num], variables: &HashMap<String, Num>, functions: &HashMap<String, Function>) -> Result<Num, EvalError> {
+        self.call_depth(name, args, variables, functions, 0)
+    }
+
+    fn call_depth(&self, name: &str, args: &[Num], variables: &HashMap<String, Num>, functions: &HashMap<String, Function>, depth: usize) -> Result<Num, EvalError> {
+        if depth > MAX_DEPTH {return Err(EvalError::TooDeep)};
+        if args.len() != self.params.len() {
+            return Err(EvalError::WrongArgCount(name.to_owned(), self.params.len()));
+        }
+        let scope: HashMap<String, Num> = self.params.iter().cloned().zip(args.iter().cloned()).collect();
+        self.body.eval_depth(&scope, variables, functions, depth)
+    }
+}
+
+#[cfg(test)]
+macro_rules! num {
+    ($numer:expr, $denom:expr) => {
+        Num::new(super::NumComponent::from($numer), super::NumComponent::from($denom))
+    };
+}
+
+#[test]
+fn expr_test() {
+    let vars = HashMap::new();
+    let functions = HashMap::new();
+    let eval = |s: &str| Expr::from_str(s).and_then(|e| e.eval(&HashMap::new(), &vars, &functions));
+    assert_eq!(eval("1 + 2 * 3"), Ok(num!(7, 1)));
+    assert_eq!(eval("(1 + 2) * 3"), Ok(num!(9, 1)));
+    assert_eq!(eval("-7 // 2"), Ok(num!(-3, 1)));
+    assert_eq!(eval("-7 %% 3"), Ok(num!(2, 1)));
+    assert_eq!(eval("1 / 3 * 1.5"), Ok(num!(1, 2)));
+    assert_eq!(eval("1 / 0"), Err(EvalError::Undefined));
+    assert_eq!(eval("y"), Err(EvalError::UnknownVariable("y".to_owned())));
+    assert!(matches!(eval("(1 + 2"), Err(EvalError::Syntax(_))));
+
+    assert_eq!(Expr::parse_list("1 + 2").map(|exprs| exprs.len()), Ok(1));
+    assert_eq!(Expr::parse_list("f(1, 2), -3").map(|exprs| exprs.len()), Ok(2));
+    assert_eq!(Expr::parse_list(" "), Ok(vec![]));
+    assert!(Expr::parse_list("1 2").is_err());
+}
+
+#[test]
+fn function_test() {
+    let mut vars = HashMap::new();
+    vars.insert("vref".to_owned(), num!(3300, 1));
+    let mut functions = HashMap::new();
+    let (name, mv) = Function::parse_definition("mv(x) = x * $vref / 4096").unwrap();
+    assert_eq!(mv.signature(&name), "mv(x)");
+    functions.insert(name, mv);
+    let (name, avg) = Function::parse_definition("avg(a, b) = (mv(a) + mv(b)) / 2").unwrap();
+    functions.insert(name, avg);
+
+    let call = |name: &str, args: &[Num]| functions[name].call(name, args, &vars, &functions);
+    assert_eq!(call("mv", &[num!(4096, 1)]), Ok(num!(3300, 1)));
+    assert_eq!(call("avg", &[num!(4096, 1), num!(0, 1)]), Ok(num!(1650, 1)));
+    assert_eq!(call("avg", &[num!(1, 1)]), Err(EvalError::WrongArgCount("avg".to_owned(), 2)));
+
+    let (name, forever) = Function::parse_definition("forever(x) = forever(x)").unwrap();
+    functions.insert(name, forever);
+    let call = |name: &str, args: &[Num]| functions[name].call(name, args, &vars, &functions);
+    assert_eq!(call("forever", &[num!(1, 1)]), Err(EvalError::TooDeep));
+}
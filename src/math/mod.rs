@@ -2,6 +2,7 @@ pub mod operation;
 pub mod equation;
 pub mod base;
 pub mod parsefmt;
+pub mod expr;
 
 pub type Num = num_rational::BigRational;
 pub type NumComponent = num_bigint::BigInt;
\ No newline at end of file
@@ -1,10 +1,19 @@
-#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+use super::Num;
+use num_traits::{Signed, Zero};
+use num_traits::ops::checked::*;
+use std::ops::Rem;
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Serialize, Deserialize)]
 pub enum Operation {
     Add,
     Sub,
     Mul,
     Div,
-    Mod,
+    TruncDiv,// integer division rounding toward zero (C `/`), `-7 // 2` is -3 so it pairs with `Mod`
+    Mod,// truncating remainder, sign follows the dividend (C `%`)
+    FloorMod,// floored remainder, sign follows the divisor (Python `%`)
+    EuclidMod,// euclidean remainder, never negative
 }
 
 impl Operation {
@@ -14,18 +23,62 @@ impl Operation {
             Operation::Sub => "-",
             Operation::Mul => "*",
             Operation::Div => "/",
+            Operation::TruncDiv => "//",
             Operation::Mod => "%",
+            Operation::FloorMod => "%%",
+            Operation::EuclidMod => "%%%",
+        }
+    }
+
+    // None for division by zero and overflow
+    pub fn apply(&self, left: &Num, right: &Num) -> Option<Num> {
+        let result: Num = match self {
+            Operation::Add => left.checked_add(right)?,
+            Operation::Sub => left.checked_sub(right)?,
+            Operation::Mul => left.checked_mul(right)?,
+            Operation::Div => left.checked_div(right)?,
+            Operation::TruncDiv => left.checked_div(right)?.trunc(),
+            Operation::Mod => {
+                if right.is_zero() {return None};
+                left.clone().rem(right.clone())
+            },
+            Operation::FloorMod => {
+                let quotient = left.checked_div(right)?.floor();
+                left - right * quotient
+            },
+            Operation::EuclidMod => {
+                let divisor = right.abs();
+                let quotient = left.checked_div(&divisor)?.floor();
+                left - divisor * quotient
+            },
+        };
+        Some(result)
+    }
+
+    // the operation produced by typing the same operator key again, e.g. `/` then `/` gives `//`
+    pub fn retyped(&self) -> Option<Self> {
+        match self {
+            Operation::Div => Some(Operation::TruncDiv),
+            Operation::Mod => Some(Operation::FloorMod),
+            Operation::FloorMod => Some(Operation::EuclidMod),
+            _ => None,
         }
     }
-    
-    pub fn from_str(s: &str) -> Option<Self> {
+}
+
+impl std::str::FromStr for Operation {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "+" => {Some(Operation::Add)},
-            "-" => {Some(Operation::Sub)},
-            "*" => {Some(Operation::Mul)},
-            "/" => {Some(Operation::Div)},
-            "%" => {Some(Operation::Mod)},
-            _ => {None}
+            "+" => {Ok(Operation::Add)},
+            "-" => {Ok(Operation::Sub)},
+            "*" => {Ok(Operation::Mul)},
+            "/" => {Ok(Operation::Div)},
+            "//" => {Ok(Operation::TruncDiv)},
+            "%" => {Ok(Operation::Mod)},
+            "%%" => {Ok(Operation::FloorMod)},
+            "%%%" => {Ok(Operation::EuclidMod)},
+            _ => {Err(())}
         }
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 use std::str::FromStr;
+use num_traits::{Pow, Signed, Zero};
 
 use super::{
     Num,
@@ -12,76 +13,112 @@ macro_rules! num {
     };
 }
 
-// only parse b10 numbers at the moment
+// only parse b10 numbers at the moment, either decimal like `3.14` or exact like `1/3`
 pub fn parse(s: String) -> Option<Num> {
-    let parts = s.split(".").collect::<Vec<&str>>();
-    if parts.len() == 1 {
-        let int = NumComponent::from_str(parts[0]);
-        let int = match int {
-            Ok(int) => {int},
-            Err(_) => {return None},
-        };
-        Some(num!(int, 1))
-    } else if parts.len() == 2 {
-        let int = NumComponent::from_str(parts[0]);
-        let int = match int {
-            Ok(int) => {int},
-            Err(_) => {return None},
-        };
-        let fract = parse_fract(parts[1]);
-        let fract = match fract {
-            Some(fract) => {fract},
-            None => {return None},
-        };
-        Some(num!(int, 1) + fract)
-    } else {
-        None
+    if let Some((numer, denom)) = s.split_once('/') {
+        let numer = NumComponent::from_str(numer).ok()?;
+        let denom = NumComponent::from_str(denom).ok()?;
+        if denom == NumComponent::from(0) {return None};
+        return Some(Num::new(numer, denom));
     }
+    let digits = s.strip_prefix('-').unwrap_or(&s);
+    if !digits.chars().any(|c| c.is_ascii_digit()) || !digits.chars().all(|c| c.is_ascii_digit() || c == '.') {return None};
+    if s.matches('.').count() > 1 {return None};
+    parse_digits(&s, NumberBase::Decimal)
 }
 
-fn parse_fract(s: &str) -> Option<Num> {
-    let comp = NumComponent::from_str(s);
-    let comp = match comp {
-        Ok(comp) => {comp},
-        Err(_) => {return None},
+// parses digits typed in `base`, like `-1F.8` in hexadecimal, without a prefix
+pub fn parse_digits(s: &str, base: NumberBase) -> Option<Num> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (int, fract) = s.split_once('.').unwrap_or((s, ""));
+    let digits = format!("{int}{fract}");
+    if digits.is_empty() {return Some(num!(0, 1))};
+    let numer = NumComponent::parse_bytes(digits.as_bytes(), base.place_value())?;
+    let denom = NumComponent::from(base.place_value()).pow(fract.len() as u32);
+    let n = Num::new(numer, denom);
+    Some(if negative {-n} else {n})
+}
+
+// parses a value in `base` unless it has a `0x` or `0b` prefix, or an exact fraction like `1/3`
+pub fn parse_in_base(s: &str, base: NumberBase) -> Option<Num> {
+    let s = s.trim();
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    // `BigInt` also reads signs inside, like the `-5` of `.-5` or the denominator of `1/-3`
+    if digits.contains(['-', '+']) {return None};
+    if s.contains('/') {return parse(s.to_owned())};
+    let (digits, base) = match (digits.get(..2).map(|p| p.to_ascii_lowercase()).as_deref(), digits.get(2..)) {
+        (Some("0x"), Some(rest)) => (rest, NumberBase::Hexadecimal),
+        (Some("0b"), Some(rest)) if base != NumberBase::Hexadecimal => (rest, NumberBase::Binary),
+        _ => (digits, base),
     };
-    let mut bound = NumComponent::from(10);
-    while comp >= bound {
-        bound *= 10;
+    // parse_digits reads an empty string as zero and `BigInt` allows `_` separators
+    if !digits.chars().any(|c| c.is_ascii_alphanumeric()) || digits.contains('_') {return None};
+    let n = parse_digits(digits, base)?;
+    Some(if negative {-n} else {n})
+}
+
+pub fn prefix(base: &NumberBase) -> &'static str {
+    match base {
+        NumberBase::Binary => "0b",
+        NumberBase::Decimal => "",
+        NumberBase::Hexadecimal => "0x",
     }
-    Some(Num::new(comp, bound))
 }
 
 pub fn fmt(n: Num, base: NumberBase, max_fract_places: u32) -> String {
+    let prefix = prefix(&base);
+    let digits = fmt_digits(n, base, max_fract_places);
+    match digits.strip_prefix('-') {
+        Some(digits) => format!("-{prefix}{digits}"),
+        None => format!("{prefix}{digits}"),
+    }
+}
+
+// like `fmt` but without the base prefix
+pub fn fmt_digits(n: Num, base: NumberBase, max_fract_places: u32) -> String {
+    if n.is_negative() {
+        return "-".to_owned() + fmt_digits(-n, base, max_fract_places).as_str();
+    }
+    let int = n.to_integer().to_str_radix(base.place_value()).to_uppercase();
     if n.is_integer() {
-        fmt_int(n.to_integer(), base)
+        int
     } else {
-        fmt_int(n.to_integer(), base.clone()) + "." + fmt_fract(n.fract(), base, max_fract_places).as_str()
+        int + "." + fmt_fract(n.fract(), base, max_fract_places).as_str()
     }
 }
 
-fn fmt_int(n: NumComponent, base: NumberBase) -> String {
-    match base {
-        NumberBase::Binary => {
-            format!("{n:#b}")
-        },
-        NumberBase::Decimal => {
-            format!("{n}")
-        },
-        NumberBase::Hexadecimal => {
-            format!("{n:#X}")
-        }
+// the first `max_places` digits of `n`, which is between 0 and 1, in one scaled integer division
+fn fmt_fract(n: Num, base: NumberBase, max_places: u32) -> String {
+    let scale = NumComponent::from(base.place_value()).pow(max_places);
+    let scaled = n.numer() * scale;
+    let digits = &scaled / n.denom();
+    let exact = &digits * n.denom() == scaled;
+    let digits = if digits.is_zero() {"".to_owned()} else {digits.to_str_radix(base.place_value()).to_uppercase()};
+    // the scaled division drops leading zeros, like the `0` in `0.05`
+    let out = "0".repeat(max_places as usize - digits.len()) + digits.as_str();
+    if exact {
+        out.trim_end_matches('0').to_owned()// no trailing zeros
+    } else {
+        out
     }
 }
 
-fn fmt_fract(n: Num, base: NumberBase, max_places: u32) -> String {
+// the digit-by-digit version `fmt_fract` replaced, kept to compare against
+#[cfg(test)]
+fn fmt_fract_naive(n: Num, base: NumberBase, max_places: u32) -> String {
     let mut out = "".to_owned();
 
         let mut n = n;
         let placevalue = num!(base.place_value(), 1); 
         
         for _ in 0..(max_places as usize) {// arbitrary max iteration
-            n = n * placevalue.clone();// multiply by base to get a single digit in the integer part
+            n *= placevalue.clone();// multiply by base to get a single digit in the integer part
             let int = n.trunc().to_integer();
             out += match base {// format the digit and add it to output
                 NumberBase::Binary => {format!("{:b}", int)},
@@ -115,10 +152,97 @@ fn fmt_test() {
     );
 }
 
+#[test]
+fn digits_test() {
+    assert_eq!(parse_digits("-1F.8", NumberBase::Hexadecimal), Some(num!(-63, 2)));
+    assert_eq!(parse_digits("3.05", NumberBase::Decimal), Some(num!(61, 20)));
+    assert_eq!(parse_digits("", NumberBase::Binary), Some(num!(0, 1)));
+    assert_eq!(parse_digits("12", NumberBase::Binary), None);
+
+    assert_eq!(fmt(num!(-1, 2), NumberBase::Decimal, 128), "-0.5");
+    assert_eq!(fmt(num!(-63, 2), NumberBase::Hexadecimal, 128), "-0x1F.8");
+    assert_eq!(fmt_digits(num!(-63, 2), NumberBase::Hexadecimal, 128), "-1F.8");
+}
+
 #[test]
 fn parse_test() {
     assert_eq!(
         parse("3.14159".to_owned()),
         Some(num!(314159,100000))
     );
+
+    // rationals are written to the vars file as `numer/denom`
+    assert_eq!(
+        parse("-1/3".to_owned()),
+        Some(num!(-1,3))
+    );
+    assert_eq!(parse("1/0".to_owned()), None);
+    // leading zeros in the fractional part are kept
+    assert_eq!(parse("3.05".to_owned()), Some(num!(61, 20)));
+    assert_eq!(parse("1.2.3".to_owned()), None);
+}
+
+#[test]
+fn parse_in_base_test() {
+    assert_eq!(parse_in_base("ff", NumberBase::Hexadecimal), Some(num!(255, 1)));
+    assert_eq!(parse_in_base("-0xFF.8", NumberBase::Decimal), Some(num!(-511, 2)));
+    assert_eq!(parse_in_base(" 0b101 ", NumberBase::Decimal), Some(num!(5, 1)));
+    // `0b` is a hex number, not a prefix, in hexadecimal
+    assert_eq!(parse_in_base("0b1", NumberBase::Hexadecimal), Some(num!(177, 1)));
+    assert_eq!(parse_in_base("1/3", NumberBase::Binary), Some(num!(1, 3)));
+    assert_eq!(parse_in_base("12", NumberBase::Binary), None);
+    assert_eq!(parse_in_base("", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("0x", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("--1", NumberBase::Decimal), None);
+    // a sign only goes at the very start
+    assert_eq!(parse_in_base(".-5", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("1.+5", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("0x-5", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("1/-3", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("+-1", NumberBase::Decimal), None);
+    assert_eq!(parse_in_base("+1", NumberBase::Decimal), Some(num!(1, 1)));
+    assert_eq!(parse_in_base("-.5", NumberBase::Decimal), Some(num!(-1, 2)));
+}
+
+#[test]
+fn fmt_fract_test() {
+    for (n, base) in [
+        (num!(1, 3), NumberBase::Decimal),
+        (num!(1, 20), NumberBase::Decimal),
+        (num!(1, 7), NumberBase::Hexadecimal),
+        (num!(5, 8), NumberBase::Binary),
+        (num!(1, 1000), NumberBase::Binary),
+        (num!(1, 10_i64.pow(15)), NumberBase::Decimal),
+    ] {
+        for places in [0, 1, 5, 64] {
+            assert_eq!(fmt_fract(n.clone(), base.clone(), places), fmt_fract_naive(n.clone(), base.clone(), places));
+        }
+    }
+}
+
+#[test]
+fn fmt_fract_places_test() {
+    // a hundred thousand places is one division, not a hundred thousand
+    let digits = fmt_fract(num!(1, 7), NumberBase::Decimal, 100_000);
+    assert_eq!(digits.len(), 100_000);
+    assert!(digits.as_bytes().chunks(6).all(|chunk| b"142857".starts_with(chunk)));
+    assert_eq!(fmt_fract(num!(1, 2).pow(1000), NumberBase::Binary, 100_000).len(), 1000);
+}
+
+// cargo test --release -- --ignored --nocapture fmt_fract_bench
+#[test]
+#[ignore]
+fn fmt_fract_bench() {
+    let n = num!(1, 7);
+    for places in [1_000, 10_000] {
+        let start = std::time::Instant::now();
+        fmt_fract_naive(n.clone(), NumberBase::Decimal, places);
+        println!("naive, {places} places: {:?}", start.elapsed());
+        let start = std::time::Instant::now();
+        fmt_fract(n.clone(), NumberBase::Decimal, places);
+        println!("scaled, {places} places: {:?}", start.elapsed());
+    }
+    let start = std::time::Instant::now();
+    fmt_fract(n, NumberBase::Decimal, 100_000);
+    println!("scaled, 100000 places: {:?}", start.elapsed());
 }
@@ -1,133 +1,838 @@
-use crate::math::{Num, equation::Equation, base::NumberBase, parsefmt};
+use crate::math::{Num, equation::{Equation, Context, EvalError}, expr::{Expr, Function}, base::NumberBase, parsefmt};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use crate::config::Config;
+use std::path::{Path, PathBuf};
+use crate::config::{self, Config, ConfigError, FileWatch, config_dir};
+use crate::command_line::{self, History};
+use crate::format_cache::FormatCache;
+use crate::keymap::{Action, Chord, Key, Keymap};
+use crate::workspace::{SavedEquation, Session, Workspace};
+use crate::export::{self, ExportEntry, ExportFormat};
+use crate::import::{self, ImportFormat};
+use clap::ValueEnum;
+use arboard::Clipboard;
+
+// the variable holding the M+/M-/MR/MC memory
+pub const MEMORY: &str = "mem";
+// how many equation changes can be undone
+const UNDO_LEVELS: usize = 100;
 
 // contains state that is shared across frontends
 pub struct State {
     pub equation: Equation,
     pub command: Option<String>,
     pub base: NumberBase,
+    pub percent_mode: bool,
     pub variables: HashMap<String, Num>,
+    pub functions: HashMap<String, Function>,
     pub vars_path: String,
     pub cached_equation_display: Option<String>,
+    pub format_cache: FormatCache,// formatted operands, kept across base switches
     pub config: Config,
+    pub max_fractional_places: u32,// the config's, or the open workspace's which is kept out of the config
+    pub config_watch: FileWatch,// minicalc.cfg, reloaded when it changes
+    pub config_errors: Vec<ConfigError>,// problems loading the config at startup, for the frontend to report
     pub exiting: bool,
+    pub message: Option<String>,// errors and notices for the frontend to show
+    pub listing: Option<Vec<String>>,// multi-line output like `:ls`, shown until dismissed
+    pub history: History,// executed commands
+    pub calculations: Vec<(Equation, Num)>,// evaluated equations and their results, oldest first
+    pub unsaved_calculations: bool,// calculations outside workspaces that aren't in their file yet
+    pub keymap: Keymap,
+    pub undo: Vec<Equation>,// equations before each change, newest last
+    pub workspace: Option<String>,// the name of the open workspace
+    pub terminal_clipboard: bool,// copy through the terminal instead of the system clipboard, which may be on another machine
+    pub terminal_copy: Option<String>,// text for the term frontend to send to the terminal's clipboard
+    pub saved_vars: Option<String>,// the vars file as last read or written, autosave writes when this is outdated
+    pub changed: bool,// input or a config change may have changed what autosave writes since it last ran
+    pub saved_workspace: Option<Workspace>,
+    pub global: Option<Workspace>,// the variables, functions, history and equation from outside workspaces while one is open
+    pub frontend_settings: Vec<String>,// settings of the frontend's own config, `:get` and `:set` leave them to it
 }
 
 impl Default for State {
     fn default() -> Self {
-        let conf = Config::load();
+        let (conf, config_errors) = Config::load();
+        let history = History::load(config_dir().join("history"), conf.history_size as usize);
+        Self {
+            vars_path: config_dir().join("minicalc-vars").to_string_lossy().into(),
+            config_watch: FileWatch::new(Config::path()),
+            config_errors,
+            history,
+            calculations: Workspace::load_calculations(),
+            ..Self::with_config(conf)
+        }
+    }
+}
+
+impl State {
+    // a state with `conf` that reads and writes nothing until it's given paths
+    pub fn with_config(conf: Config) -> Self {
+        let history = History::new(PathBuf::new(), conf.history_size as usize);
+        // bad chords are in the config errors with their position
+        let (keymap, _) = Keymap::new(&conf.keymap);
         Self { 
             equation: Equation::default(), 
             command: None, 
             base: conf.base.clone(),
+            percent_mode: conf.percent_mode,
             variables: HashMap::new(), 
-            vars_path: "minicalc-vars".to_owned(),
+            functions: HashMap::new(),
+            vars_path: String::new(),
             cached_equation_display: None,
+            format_cache: FormatCache::default(),
+            max_fractional_places: conf.max_fractional_places,
             config: conf,
+            config_watch: FileWatch::new(PathBuf::new()),
+            config_errors: vec![],
             exiting: false,
+            message: None,
+            listing: None,
+            history,
+            calculations: vec![],
+            unsaved_calculations: false,
+            keymap,
+            undo: vec![],
+            workspace: None,
+            terminal_clipboard: false,
+            terminal_copy: None,
+            saved_vars: None,
+            changed: false,
+            saved_workspace: None,
+            global: None,
+            frontend_settings: vec![],
         }
     }
-}
-
-impl State {
     pub fn display(&mut self) -> String {
-        if self.command.is_some() {
-            format!(":{}", self.command.as_ref().unwrap())
+        if let Some(command) = &self.command {
+            format!(":{}", command)
         } else {
             if self.cached_equation_display.is_some() {
                 self.cached_equation_display.clone().unwrap()
             } else {
-                let display = self.equation.display(self.base.clone(), self.config.max_fractional_places);
+                let (base, places) = (&self.base, self.max_fractional_places);
+                let cache = &mut self.format_cache;
+                let display = self.equation.display_with(|n| cache.get(n, base, places));
                 self.cached_equation_display = Some(display.clone());
                 display
             }
         }
     } 
+    // `n` in the current base, possibly a preview while the worker formats it
+    pub fn format(&mut self, n: &Num) -> String {
+        self.format_cache.get(n, &self.base, self.max_fractional_places)
+    }
+    // picks up formats finished in the background, true if the display changed
+    pub fn poll_formats(&mut self) -> bool {
+        let finished = self.format_cache.poll();
+        if finished {
+            self.cached_equation_display = None;
+        }
+        finished
+    }
+    pub fn context(&self) -> Context<'_> {
+        Context {
+            base: self.base.clone(),
+            max_fract_places: self.max_fractional_places,
+            percent_mode: self.percent_mode,
+            chain: self.config.chain_operations,
+            repeat: self.config.repeat_on_enter,
+            variables: &self.variables,
+        }
+    }
+    // runs `f` on the equation with a context borrowed from the rest of the state
+    fn with_equation<R>(&mut self, f: impl FnOnce(&mut Equation, &Context) -> R) -> R {
+        let mut equation = std::mem::take(&mut self.equation);
+        let r = f(&mut equation, &self.context());
+        self.equation = equation;
+        r
+    }
+    // keeps `before` for undo if the equation changed since
+    fn remember(&mut self, before: Equation) {
+        if before == self.equation {return};
+        self.undo.push(before);
+        if self.undo.len() > UNDO_LEVELS {
+            self.undo.remove(0);
+        }
+    }
+    pub fn undo(&mut self) {
+        if let Some(equation) = self.undo.pop() {
+            self.equation = equation;
+            self.cached_equation_display = None;
+        }
+    }
+    // the action bound to `chord`, characters are just typed while entering a command
+    pub fn key_action(&self, chord: &Chord) -> Option<Action> {
+        if self.command.is_some() && matches!(chord.key, Key::Char(_) | Key::Space) && !chord.ctrl && !chord.alt {return None};
+        self.keymap.get(chord)
+    }
+    // runs a keymap action, false for ones the frontend has to handle like executing a command
+    pub fn perform(&mut self, action: Action) -> bool {
+        self.changed = true;
+        match action {
+            Action::Evaluate if self.command.is_some() => return false,
+            Action::Evaluate => self.evaluate(),
+            Action::Delete => self.delete_one(),
+            Action::DeleteForward => self.delete_forward(),
+            Action::ToggleBase => self.cycle_base(),
+            Action::BaseBinary => self.set_base(NumberBase::Binary),
+            Action::BaseDecimal => self.set_base(NumberBase::Decimal),
+            Action::BaseHexadecimal => self.set_base(NumberBase::Hexadecimal),
+            Action::Copy => self.copy_equation(),
+            Action::Paste => self.paste(),
+            Action::Undo => self.undo(),
+            Action::Store => self.memory_store(),
+            Action::CommandMode => self.enter_command_entry("".to_owned()),
+            Action::Cancel if self.command.is_some() => self.enter_equation_entry(),
+            Action::Cancel => self.listing = None,
+            Action::Complete => self.complete_command(&[]),
+            Action::HistoryPrev => self.history_prev(),
+            Action::HistoryNext => self.history_next(),
+            Action::CursorLeft => self.move_cursor(|cursor, _| cursor.saturating_sub(1)),
+            Action::CursorRight => self.move_cursor(|cursor, _| cursor + 1),
+            Action::CursorHome => self.move_cursor(|_, _| 0),
+            Action::CursorEnd => self.move_cursor(|_, len| len),
+            Action::MemoryAdd => self.memory_add(),
+            Action::MemorySub => self.memory_sub(),
+            Action::MemoryRecall => self.memory_recall(),
+            Action::MemoryClear => self.memory_clear(),
+            Action::Unbound => return false,
+        }
+        true
+    }
+    // re-reads minicalc.cfg if it changed on disk
+    pub fn reload_config(&mut self) {
+        if !self.config_watch.changed() {return};
+        self.read_config();
+    }
+    // switches to minicalc.cfg as it is now, keeping the current config if it's malformed
+    fn read_config(&mut self) {
+        match Config::read() {
+            Ok(config) => {
+                self.message = Some("reloaded config".to_owned());
+                self.apply_config(config);
+            },
+            Err(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                self.message = Some(format!("config not reloaded: {}", errors.join(", ")));
+            },
+        }
+    }
+    // switches to `config`, picking up the changes that are cached elsewhere in the state
+    pub fn apply_config(&mut self, config: Config) {
+        self.changed = true;
+        if config.base != self.config.base {self.set_base(config.base.clone())};
+        if config.percent_mode != self.config.percent_mode {self.percent_mode = config.percent_mode};
+        self.history.set_max_len(config.history_size as usize);
+        let fewer_ans = config.ans_registers < self.config.ans_registers;
+        if self.workspace.is_none() {self.max_fractional_places = config.max_fractional_places};
+        self.config = config;
+        if fewer_ans {self.trim_ans()};
+        let (keymap, errors) = Keymap::new(&self.config.keymap);
+        self.keymap = keymap;
+        if !errors.is_empty() {
+            self.message = Some(format!("keymap: {}", errors.join(", ")));
+        }
+        self.cached_equation_display = None;
+    }
+    pub fn set_base(&mut self, base: NumberBase) {
+        // the digits being typed are in the old base
+        if let Err(e) = self.equation.commit() {
+            self.message = Some(e.to_string());
+        }
+        self.base = base;
+        self.cached_equation_display = None;
+    }
+    // the base after the current one in `base_cycle`, or the first one if it isn't in there
+    pub fn cycle_base(&mut self) {
+        let cycle = &self.config.base_cycle;
+        let next = match cycle.iter().position(|base| *base == self.base) {
+            Some(i) => cycle.get((i + 1) % cycle.len()),
+            None => cycle.first(),
+        };
+        if let Some(base) = next.cloned() {
+            self.set_base(base);
+        }
+    }
+    // `what` names the text in the message, like `equation`
+    fn copy_text(&mut self, text: String, what: &str) {
+        if self.terminal_clipboard {
+            self.terminal_copy = Some(text);
+            self.message = Some(format!("copied {what}"));
+            return
+        }
+        let copied = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+        self.message = Some(if copied.is_ok() {format!("copied {what}")} else {"failed to copy".to_owned()});
+    }
+    pub fn copy_equation(&mut self) {
+        let text = self.equation.display(self.base.clone(), self.max_fractional_places);
+        self.copy_text(text, "equation");
+    }
+    pub fn copy_result(&mut self) {
+        match self.current_result() {
+            Ok(n) => self.copy_text(self.format_num(&n), "result"),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+    pub fn paste(&mut self) {
+        // the system clipboard may be on another machine, the terminal's own paste arrives as a bracketed paste
+        if self.terminal_clipboard {
+            self.message = Some("paste with the terminal's paste shortcut".to_owned());
+            return
+        }
+        match Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => self.type_string(text),
+            Err(_) => self.message = Some("failed to paste".to_owned()),
+        }
+    }
     pub fn try_type_single(&mut self, char: char) {
-        self.equation.try_type_single(char.to_uppercase().next().unwrap().to_string().as_str(), self.base.clone(), self.config.max_fractional_places) 
+        let before = self.equation.clone();
+        let chained = self.with_equation(|equation, ctx| equation.try_type_single(char.to_string().as_str(), ctx));
+        match chained {
+            // an operator typed after a complete equation evaluated it
+            Ok(Some(result)) => {
+                self.push_calculation(before, result.clone());
+                self.push_ans(result);
+            },
+            Ok(None) => {},
+            Err(e) => self.message = Some(e.to_string()),
+        }
     }
     pub fn enter_command_entry(&mut self, command: String) {
         self.command = Some(command);
     }
     pub fn enter_equation_entry(&mut self) {
         self.command = None;
+        self.history.reset();
     }
-    pub fn write_vars(&mut self) {
+    // completes the command being typed, `extra_commands` are ones only a frontend understands
+    pub fn complete_command(&mut self, extra_commands: &[&str]) {
+        let command = match &self.command {
+            Some(command) => command,
+            None => return,
+        };
+        let mut commands = command_line::COMMANDS.to_vec();
+        commands.extend_from_slice(extra_commands);
+        let names: Vec<&str> = self.variables.keys().chain(self.functions.keys()).map(|name| name.as_str()).collect();
+        let completion = command_line::complete(command, &commands, &names);
+        self.command = Some(completion.line);
+        if !completion.candidates.is_empty() {
+            self.message = Some(completion.candidates.join(" "));
+        }
+    }
+    pub fn history_prev(&mut self) {
+        if let Some(command) = &self.command {
+            if let Some(prev) = self.history.older(command) {
+                self.command = Some(prev);
+            }
+        }
+    }
+    pub fn history_next(&mut self) {
+        if self.command.is_some() {
+            if let Some(next) = self.history.newer() {
+                self.command = Some(next);
+            }
+        }
+    }
+    // evaluates the equation, shifting the result into the `ans` registers
+    pub fn evaluate(&mut self) {
+        let before = self.equation.clone();
+        let r = self.with_equation(|equation, ctx| equation.eval_mut(ctx));
+        match r {
+            Ok(()) => {
+                // repeating the last operation has nothing to show for an expression
+                if before.operation.is_some() {
+                    self.push_calculation(before.clone(), self.equation.left.clone());
+                }
+                self.push_ans(self.equation.left.clone());
+            },
+            Err(EvalError::Incomplete) => {},
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        self.remember(before);
+        self.cached_equation_display = None;
+    }
+    // `equation` as it was before evaluating, it's only formatted when exported
+    fn push_calculation(&mut self, equation: Equation, result: Num) {
+        self.calculations.push((equation, result));
+        let max_len = self.config.history_size as usize;
+        if self.calculations.len() > max_len {
+            self.calculations.drain(..self.calculations.len() - max_len);
+        }
+        self.unsaved_calculations |= self.workspace.is_none();
+    }
+    // calculations outside workspaces are written to their own file, a workspace's are saved with it
+    fn save_calculations(&mut self) {
+        if !self.unsaved_calculations || self.workspace.is_some() {return};
+        match Workspace::save_calculations(&self.calculations) {
+            Ok(()) => self.unsaved_calculations = false,
+            Err(_) => self.message = Some("failed writing calculations".to_owned()),
+        }
+    }
+    // the variables by name, then the calculations, with values in `base`
+    pub fn export_entries(&self, base: &NumberBase) -> Vec<ExportEntry> {
+        let mut names: Vec<&String> = self.variables.keys().collect();
+        names.sort();
+        let variables = names.into_iter().map(|name| ("variable", name.clone(), &self.variables[name]));
+        let calculations = self.calculations.iter().map(|(equation, n)| {
+            // typed digits are in the base they were typed in, committed they're formatted in `base` like the values
+            let mut committed = equation.clone();
+            let committed = if committed.commit().is_ok() {committed} else {equation.clone()};
+            ("calculation", committed.display(base.clone(), self.max_fractional_places), n)
+        });
+        variables.chain(calculations).map(|(kind, expression, n)| {
+            let value = parsefmt::fmt(n.clone(), base.clone(), self.max_fractional_places);
+            ExportEntry {kind, expression, exact: n.to_string(), value}
+        }).collect()
+    }
+    pub fn export(&mut self, format: ExportFormat, base: &NumberBase, path: &str) {
+        let data = export::export(&self.export_entries(base), format);
+        match fs::write(path, data) {
+            Ok(()) => self.message = Some(format!("exported to '{path}'")),
+            Err(_) => self.message = Some(format!("failed writing '{path}'")),
+        }
+    }
+    // `ans` is the latest result, `ans1` the one before it, up to `ans{ans_registers}`
+    fn push_ans(&mut self, n: Num) {
+        for i in (1..=self.config.ans_registers).rev() {
+            let previous = if i == 1 {"ans".to_owned()} else {format!("ans{}", i - 1)};
+            if let Some(previous) = self.variables.get(&previous).cloned() {
+                self.variables.insert(format!("ans{i}"), previous);
+            }
+        }
+        self.variables.insert("ans".to_owned(), n);
+        self.trim_ans();
+    }
+    // drops the registers past `ans_registers`, left over from when it was higher
+    fn trim_ans(&mut self) {
+        let max = self.config.ans_registers;
+        self.variables.retain(|name, _| {
+            let register = name.strip_prefix("ans").filter(|_| Self::is_ans_register(name)).and_then(|i| i.parse::<u32>().ok());
+            register.is_none_or(|i| i <= max)
+        });
+    }
+    pub fn memory(&self) -> Option<&Num> {
+        self.variables.get(MEMORY)
+    }
+    // the value memory operations act on, the equation result if there is one
+    fn current_result(&self) -> Result<Num, EvalError> {
+        let resolved = self.equation.resolve(&self.variables)?;
+        // the untouched right operand of `5*` is a placeholder, not a 0 to evaluate with
+        if self.equation.right_untouched() {return Ok(resolved.left)};
+        match self.equation.eval(&self.variables) {
+            Err(EvalError::Incomplete) => Ok(resolved.editing_num()),
+            r => Ok(r?.left),
+        }
+    }
+    pub fn memory_add(&mut self) {
+        let memory = self.memory().cloned().unwrap_or_default();
+        match self.current_result() {
+            Ok(n) => {self.variables.insert(MEMORY.to_owned(), memory + n);},
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+    pub fn memory_sub(&mut self) {
+        let memory = self.memory().cloned().unwrap_or_default();
+        match self.current_result() {
+            Ok(n) => {self.variables.insert(MEMORY.to_owned(), memory - n);},
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+    pub fn memory_recall(&mut self) {
+        if let Some(memory) = self.memory().cloned() {
+            let before = self.equation.clone();
+            self.equation.set_editing_num(memory);
+            self.remember(before);
+            self.cached_equation_display = None;
+        }
+    }
+    pub fn memory_store(&mut self) {
+        match self.current_result() {
+            Ok(n) => {self.variables.insert(MEMORY.to_owned(), n);},
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+    pub fn memory_clear(&mut self) {
+        self.variables.remove(MEMORY);
+    }
+    pub fn is_ans_register(name: &str) -> bool {
+        name.strip_prefix("ans").is_some_and(|i| i.chars().all(|c| c.is_ascii_digit()))
+    }
+    // the text of the vars file, variables are a name line and a value line, functions a `fn name(params)` line and a body line,
+    // names never have spaces so the `fn ` can't be part of one
+    fn vars_text(&self) -> String {
+        let (variables, functions) = (&self.variables, &self.functions);
+        let mut names: Vec<&String> = variables.keys().collect();
+        names.sort();
         let mut out = String::new();
-        for (name, n) in self.variables.iter() {
-            out += format!("{}\n{}\n",name,n).as_str();
+        for name in names {
+            if !self.config.persist_ans && Self::is_ans_register(name) {continue};
+            out += format!("{}\n{}\n", name, variables[name]).as_str();
+        }
+        let mut names: Vec<&String> = functions.keys().collect();
+        names.sort();
+        for name in names {
+            let function = &functions[name];
+            out += format!("fn {}\n{}\n", function.signature(name), function.source).as_str();
+        }
+        out
+    }
+    // the vars file holds the variables from outside workspaces, so it's only read and written outside them
+    fn outside_workspace(&mut self, command: &str) -> bool {
+        match &self.workspace {
+            Some(name) => {
+                self.message = Some(format!("':{command}' doesn't work in workspace '{name}', use ':ws close' first"));
+                false
+            },
+            None => true,
+        }
+    }
+    pub fn write_vars(&mut self) {
+        let out = self.vars_text();
+        let res = config::write_atomic(Path::new(&self.vars_path), &out);
+        if res.is_err() {
+            self.message = Some(format!("failed writing vars '{}'", self.vars_path));
+        } else {
+            self.message = Some(format!("wrote vars '{}'", self.vars_path));
+            self.saved_vars = Some(out);
         }
-        _ = fs::write(self.vars_path.clone(), out);
     } 
+    // with the `autosave` config, writes the open workspace, or the variables and calculations outside one, if they changed since last time,
+    // they're only serialized to compare after input that may have changed them
+    pub fn autosave(&mut self) {
+        if !self.config.autosave || !self.changed {return};
+        self.changed = false;
+        self.save_calculations();
+        let name = match &self.workspace {
+            Some(name) => name.clone(),
+            None => {
+                let vars = self.vars_text();
+                if self.saved_vars.as_ref() != Some(&vars) {
+                    match config::write_atomic(Path::new(&self.vars_path), &vars) {
+                        Ok(()) => self.saved_vars = Some(vars),
+                        Err(_) => self.message = Some(format!("failed autosaving vars '{}'", self.vars_path)),
+                    }
+                }
+                return
+            },
+        };
+        let workspace = self.to_workspace();
+        if self.saved_workspace.as_ref() != Some(&workspace) {
+            match workspace.save(&name) {
+                Ok(()) => self.saved_workspace = Some(workspace),
+                Err(e) => self.message = Some(e),
+            }
+        }
+    }
     pub fn read_vars(&mut self) {
         let data = fs::read_to_string(self.vars_path.clone());
         if data.is_err() {
+            self.message = Some(format!("no file '{}'", self.vars_path));
             return
         }
-        self.variables = HashMap::new();
+        let mut variables = HashMap::new();
+        let mut functions = HashMap::new();
         let data = data.unwrap();
         let lines: Vec<&str> = data.lines().collect();
-        for pair in lines.chunks(2) {
+        let mut skipped = vec![];// line numbers of names that couldn't be read
+        for (i, pair) in lines.chunks(2).enumerate() {
+            if pair.len() < 2 {
+                skipped.push(i * 2 + 1);
+                continue
+            };
+            if let Some(signature) = pair[0].strip_prefix("fn ") {
+                match Function::parse_definition(format!("{} = {}", signature, pair[1]).as_str()) {
+                    Ok((name, function)) => {functions.insert(name, function);},
+                    Err(_) => skipped.push(i * 2 + 1),
+                }
+                continue;
+            }
             let n = parsefmt::parse(pair[1].to_owned());
             let n = match n {
                 Some(n) => {n},
-                None => {continue},
+                None => {
+                    skipped.push(i * 2 + 1);
+                    continue
+                },
             };
-            self.variables.insert(pair[0].to_owned(), n);
+            variables.insert(pair[0].to_owned(), n);
+        }
+        self.variables = variables;
+        self.functions = functions;
+        self.message = Some(if skipped.is_empty() {
+            format!("read vars '{}'", self.vars_path)
+        } else {
+            let lines: Vec<String> = skipped.iter().map(|line| line.to_string()).collect();
+            format!("read vars '{}', skipped the entries at lines {}", self.vars_path, lines.join(", "))
+        });
+        self.saved_vars = Some(self.vars_text());
+    }
+    // with the `restore_session` config, goes back to where the last session was closed
+    pub fn restore_session(&mut self) {
+        if !self.config.restore_session {return};
+        let session = match Session::load() {
+            Some(session) => session,
+            None => return,
+        };
+        // the session is newer than the workspace file unless it was autosaved
+        if let Some(name) = session.workspace {
+            self.open_workspace(&name);
+        }
+        self.equation = session.equation.equation().unwrap_or_default();
+        self.base = session.base;
+        self.percent_mode = session.percent_mode;
+        self.cached_equation_display = None;
+    }
+    // saves what there is to save before minicalc closes
+    pub fn before_exit(&mut self) {
+        self.changed = true;
+        self.autosave();
+        self.save_calculations();
+        if !self.config.restore_session {return};
+        let session = Session {
+            equation: SavedEquation::new(&self.equation),
+            base: self.base.clone(),
+            percent_mode: self.percent_mode,
+            workspace: self.workspace.clone(),
+        };
+        if session.save().is_err() {
+            self.message = Some("failed saving the session".to_owned());
+        }
+    }
+    // the state a workspace is saved with, `ans` registers only with the `persist_ans` config like the vars file
+    pub fn to_workspace(&self) -> Workspace {
+        let variables = self.variables.iter()
+            .filter(|(name, _)| self.config.persist_ans || !Self::is_ans_register(name))
+            .map(|(name, n)| (name.clone(), n.clone()))
+            .collect();
+        Workspace {
+            variables: Workspace::saved_variables(&variables),
+            functions: Workspace::saved_functions(&self.functions),
+            history: self.history.entries.clone(),
+            calculations: Workspace::saved_calculations(&self.calculations),
+            equation: SavedEquation::new(&self.equation),
+            base: self.base.clone(),
+            percent_mode: self.percent_mode,
+            max_fractional_places: self.max_fractional_places,
+        }
+    }
+    // replaces everything a workspace holds, functions that don't parse are skipped
+    pub fn apply_workspace(&mut self, workspace: Workspace) {
+        self.variables = workspace.parsed_variables().collect();
+        self.functions = workspace.parsed_functions().collect();
+        self.calculations = workspace.parsed_calculations().collect();
+        self.history.replace(workspace.history);
+        // a workspace's history is saved with it
+        self.history.persist = self.global.is_none();
+        self.equation = workspace.equation.equation().unwrap_or_default();
+        self.undo = vec![];
+        self.base = workspace.base;
+        self.percent_mode = workspace.percent_mode;
+        self.max_fractional_places = workspace.max_fractional_places.min(config::MAX_FRACTIONAL_PLACES);
+        self.cached_equation_display = None;
+    }
+    // saves the open workspace first, so switching back picks up where it was left
+    pub fn open_workspace(&mut self, name: &str) {
+        match Workspace::open(name) {
+            Ok(workspace) => {
+                self.leave_outside();
+                self.apply_workspace(workspace.clone());
+                self.saved_workspace = Some(workspace);
+                self.workspace = Some(name.to_owned());
+                self.message = Some(format!("opened workspace '{name}'"));
+            },
+            Err(e) => self.message = Some(e),
         }
     }
+    // saves as `name`, or the open workspace without one
+    pub fn save_workspace(&mut self, name: Option<&str>) {
+        let name = match name.or(self.workspace.as_deref()) {
+            Some(name) => name.to_owned(),
+            None => return,
+        };
+        let workspace = self.to_workspace();
+        match workspace.save(&name) {
+            Ok(()) => {
+                // saving as a workspace from outside one goes on in it
+                if self.workspace.is_none() {
+                    self.global = Some(workspace.clone());
+                    self.history.persist = false;
+                }
+                self.saved_workspace = Some(workspace);
+                self.message = Some(format!("saved workspace '{name}'"));
+                self.workspace = Some(name);
+            },
+            Err(e) => self.message = Some(e),
+        }
+    }
+    // starts an empty workspace with the settings from the config
+    pub fn new_workspace(&mut self, name: &str) {
+        if Workspace::exists(name) {
+            self.message = Some(format!("workspace '{name}' already exists"));
+            return
+        }
+        if let Err(e) = Workspace::path(name) {
+            self.message = Some(e);
+            return
+        }
+        self.leave_outside();
+        self.apply_workspace(Workspace::from_config(&self.config));
+        // already in the workspace, so saving it leaves the state from outside alone
+        self.workspace = Some(name.to_owned());
+        self.save_workspace(None);
+    }
+    // saves the open workspace, or keeps the state from outside workspaces to come back to
+    fn leave_outside(&mut self) {
+        if self.workspace.is_some() {
+            self.save_workspace(None);
+        } else {
+            self.save_calculations();
+            // kept in memory, so the `ans` registers come back too
+            let variables = Workspace::saved_variables(&self.variables);
+            self.global = Some(Workspace {variables, ..self.to_workspace()});
+        }
+    }
+    // saves the open workspace and goes back to the variables, history and equation from before it was opened
+    pub fn close_workspace(&mut self) {
+        let name = match &self.workspace {
+            Some(name) => name.clone(),
+            None => {
+                self.message = Some("no workspace open".to_owned());
+                return
+            },
+        };
+        self.save_workspace(None);
+        let global = self.global.take().unwrap_or_else(|| Workspace::from_config(&self.config));
+        self.apply_workspace(global);
+        // the config may have changed while the workspace was open
+        self.max_fractional_places = self.config.max_fractional_places;
+        self.workspace = None;
+        self.saved_workspace = None;
+        self.message = Some(format!("closed workspace '{name}'"));
+    }
+    fn format_num(&self, n: &Num) -> String {
+        parsefmt::fmt(n.clone(), self.base.clone(), self.max_fractional_places)
+    }
+    // `name = value` lines for variables and definitions for functions whose name matches `pattern`
+    fn list(&mut self, pattern: &str) -> Vec<String> {
+        let mut names: Vec<&String> = self.variables.keys().filter(|name| matches_pattern(name, pattern)).collect();
+        names.sort();
+        let (base, places, cache) = (&self.base, self.max_fractional_places, &mut self.format_cache);
+        let mut lines: Vec<String> = names.iter().map(|name| format!("{} = {}", name, cache.get(&self.variables[*name], base, places))).collect();
+        let mut names: Vec<&String> = self.functions.keys().filter(|name| matches_pattern(name, pattern)).collect();
+        names.sort();
+        lines.extend(names.iter().map(|name| format!("{} = {}", self.functions[*name].signature(name), self.functions[*name].source)));
+        lines
+    }
+    // applies a user function to the operand being edited, `args` like `1 + 2, $rate` fill the parameters after the first
+    fn call_function(&mut self, name: &str, args: &str) -> Result<(), EvalError> {
+        let function = self.functions.get(name).ok_or(EvalError::UnknownFunction(name.to_owned()))?;
+        let mut values = vec![self.equation.resolve(&self.variables)?.editing_num()];
+        for arg in Expr::parse_list(args)? {
+            values.push(arg.eval(&HashMap::new(), &self.variables, &self.functions)?);
+        }
+        let result = function.call(name, &values, &self.variables, &self.functions)?;
+        self.equation.set_editing_num(result);
+        self.cached_equation_display = None;
+        Ok(())
+    }
     pub fn delete_one(&mut self) {
         if self.command.is_some() {
             let c = self.command.as_mut().unwrap();
-            if c.len() > 0 {
-                *c = c[..c.len()-1].to_owned();
-            } else {
+            if c.pop().is_none() {
                 self.command = None;
             }
         } else {
-            self.equation.delete_one_mut(self.base.clone(), self.config.max_fractional_places);
+            let before = self.equation.clone();
+            self.with_equation(|equation, ctx| equation.delete_one_mut(ctx));
+            self.remember(before);
         }
         self.cached_equation_display = None;
     }
+    pub fn delete_forward(&mut self) {
+        if self.command.is_some() {return};
+        let before = self.equation.clone();
+        self.with_equation(|equation, ctx| equation.delete_forward(ctx));
+        self.remember(before);
+        self.cached_equation_display = None;
+    }
+    // moves the cursor in the operand being typed, `to` gets the cursor and the number of digits
+    pub fn move_cursor(&mut self, to: impl FnOnce(usize, usize) -> usize) {
+        if self.command.is_some() {return};
+        self.with_equation(|equation, ctx| equation.move_cursor(to, ctx));
+        self.cached_equation_display = None;
+    }
     pub fn type_string(&mut self, text: String) {
+        self.changed = true;
         let text = text.replace("\n", "");
-        if self.command.is_some() {
-            *self.command.as_mut().unwrap() += text.as_str();
+        if let Some(command) = &mut self.command {
+            *command += text.as_str();
         } else {
-            if text.starts_with(":") {
+            if let Some(text) = text.strip_prefix(":") {
                 self.command = Some("".to_owned());
-                self.type_string(text[1..].to_owned());
+                self.type_string(text.to_owned());
+                return;
             }
+            let before = self.equation.clone();
             for char in text.chars() {
                 self.try_type_single(char);
             }
+            self.remember(before);
         }
         self.cached_equation_display = None;// invalidate the cached display
     }
     pub fn execute_command(&mut self) {
+        self.changed = true;
+        let before = self.equation.clone();
+        let workspace = self.workspace.clone();
+        self.run_command();
+        // opening, starting or closing a workspace replaced the equation and its undo history
+        if self.workspace == workspace {self.remember(before)};
+    }
+    fn run_command(&mut self) {
         let command = self.command.clone().unwrap_or("".to_owned());
+        self.listing = None;
+        self.history.push(&command);
         match command.trim() {
             // single string commands with no arguments
             "b" | "binary" | "b2"  => {
-                self.base = NumberBase::Binary; 
-                self.cached_equation_display = None;
+                self.set_base(NumberBase::Binary);
             },
             "x" | "hex" | "hexadecimal" | "b16" => {
-                self.base = NumberBase::Hexadecimal; 
-                self.cached_equation_display = None;
+                self.set_base(NumberBase::Hexadecimal);
             },
             "d" | "decimal" | "b10" => {
-                self.base = NumberBase::Decimal; 
-                self.cached_equation_display = None;
+                self.set_base(NumberBase::Decimal);
+            },
+            "pct" | "percent" => {
+                self.percent_mode = !self.percent_mode;
+            },
+            "delta" | "pctchange" => {
+                match self.equation.percent_change(&self.variables) {
+                    Ok(change) => {
+                        self.equation = Equation {left: change, ..Default::default()};
+                        self.cached_equation_display = None;
+                    },
+                    Err(e) => self.message = Some(e.to_string()),
+                }
             },
             "q" | "quit" | "exit" => {self.exiting = true},
-            "w" | "write" => {self.write_vars()},
-            "r" | "read" => {self.read_vars()},
+            "w" | "write" => {if self.outside_workspace("w") {self.write_vars()}},
+            "r" | "read" => {if self.outside_workspace("r") {self.read_vars()}},
             "c" | "clear" => {self.variables = HashMap::new()},
-            "wq" => {self.write_vars(); self.exiting = true},
+            "fns" | "functions" => {
+                let mut definitions: Vec<String> = self.functions.iter()
+                    .map(|(name, function)| format!("{} = {}", function.signature(name), function.source))
+                    .collect();
+                definitions.sort();
+                self.message = Some(if definitions.is_empty() {"no functions".to_owned()} else {definitions.join("; ")});
+            },
+            "wq" => {
+                // a workspace is saved instead of the vars file
+                if self.workspace.is_some() {self.save_workspace(None)} else {self.write_vars()};
+                self.exiting = true;
+            },
+            "y" | "yank" => {self.copy_equation()},
             "" => {},// skip this case before we do any other logic
             _ => {
                 let mut args = command.split(" ");
@@ -148,19 +853,26 @@ impl State {
                             None => {break 's_case}
                         };
 
+                        let resolved = match self.equation.resolve(&self.variables) {
+                            Ok(resolved) => {resolved},
+                            Err(e) => {
+                                self.message = Some(e.to_string());
+                                break 's_case
+                            }
+                        };
                         match side {
                             "l" | "left" => {
-                                self.variables.insert(name.to_owned(), self.equation.left.clone());
+                                self.variables.insert(name.to_owned(), resolved.left);
                             },
                             "r" | "right" => {
                                 if self.equation.editing_left() {
                                     break 's_case
                                 };
-                                self.variables.insert(name.to_owned(), self.equation.right.clone().unwrap());
+                                self.variables.insert(name.to_owned(), resolved.right.unwrap());
                             },
                             "R" | "result" => {
-                                let r = self.equation.eval();
-                                if let Some(result) = r {
+                                let r = self.equation.eval(&self.variables);
+                                if let Ok(result) = r {
                                     self.variables.insert(name.to_owned(), result.left);
                                 } 
                             },
@@ -189,33 +901,244 @@ impl State {
 
                         match side {
                             "l" | "left" => {
-                                self.equation.left = self.variables.get(name).unwrap().clone();
+                                self.equation.set_left(self.variables.get(name).unwrap().clone());
                                 self.cached_equation_display = None;
                             },
                             "r" | "right" => {
                                 if self.equation.editing_left() {
                                     break 'l_case
                                 };
-                                self.equation.right = Some(self.variables.get(name).unwrap().clone());
+                                self.equation.set_right(self.variables.get(name).unwrap().clone());
                                 self.cached_equation_display = None;
                             },
                             _ => {}
                         }
                     },
+                    "markup" | "discount" => 'markup_case: {
+                        let r = args.next().and_then(|percent| parsefmt::parse(percent.to_owned()));
+                        let percent = match r {
+                            Some(percent) => {percent},
+                            None => {break 'markup_case}
+                        };
+                        let percent = if command.starts_with("markup") {percent} else {-percent};
+                        if let Err(e) = self.equation.apply_markup(&percent, &self.variables) {
+                            self.message = Some(e.to_string());
+                        }
+                        self.cached_equation_display = None;
+                    },
+                    "ls" | "list" => {
+                        let pattern = args.next().unwrap_or("");
+                        let lines = self.list(pattern);
+                        if lines.is_empty() {
+                            self.message = Some("no variables".to_owned());
+                        } else {
+                            self.listing = Some(lines);
+                        }
+                    },
+                    "show" => {
+                        let (base, places, cache) = (&self.base, self.max_fractional_places, &mut self.format_cache);
+                        let lines: Vec<String> = args.filter(|name| !name.is_empty()).flat_map(|name| {
+                            match self.variables.get(name) {
+                                Some(n) => vec![format!("{} = {}", name, cache.get(n, base, places))],
+                                None => match self.functions.get(name) {
+                                    Some(function) => vec![format!("{} = {}", function.signature(name), function.source)],
+                                    None => vec![format!("no variable '{name}'")],
+                                },
+                            }
+                        }).collect();
+                        if !lines.is_empty() {self.listing = Some(lines)};
+                    },
+                    "rm" | "del" => {
+                        for name in args.filter(|name| !name.is_empty()) {
+                            if self.variables.remove(name).is_none() && self.functions.remove(name).is_none() {
+                                self.message = Some(format!("no variable '{name}'"));
+                            }
+                        }
+                    },
+                    // `mv!` replaces an existing variable or function
+                    "mv" | "rename" | "mv!" | "rename!" => 'mv_case: {
+                        let force = command.split(' ').next().is_some_and(|word| word.ends_with('!'));
+                        let (old, new) = match (args.next(), args.next()) {
+                            (Some(old), Some(new)) => {(old, new)},
+                            _ => {break 'mv_case}
+                        };
+                        if !self.variables.contains_key(old) && !self.functions.contains_key(old) {
+                            self.message = Some(format!("no variable '{old}'"));
+                            break 'mv_case
+                        }
+                        if old == new {break 'mv_case};
+                        let exists = self.variables.contains_key(new) || self.functions.contains_key(new);
+                        if exists && !force {
+                            self.message = Some(format!("'{new}' already exists, use mv! to replace it"));
+                            break 'mv_case
+                        }
+                        self.variables.remove(new);
+                        self.functions.remove(new);
+                        if let Some(n) = self.variables.remove(old) {
+                            self.variables.insert(new.to_owned(), n);
+                        } else if let Some(function) = self.functions.remove(old) {
+                            self.functions.insert(new.to_owned(), function);
+                        }
+                    },
+                    "def" | "define" => {
+                        let remaining: Vec<&str> = args.collect();
+                        match Function::parse_definition(remaining.join(" ").as_str()) {
+                            Ok((name, function)) => {
+                                self.message = Some(format!("defined {}", function.signature(&name)));
+                                self.functions.insert(name, function);
+                            },
+                            Err(e) => self.message = Some(e.to_string()),
+                        }
+                    },
+                    "undef" => {
+                        for name in args {
+                            if self.functions.remove(name).is_none() {
+                                self.message = Some(EvalError::UnknownFunction(name.to_owned()).to_string());
+                            }
+                        }
+                    },
+                    "fn" | "call" => 'fn_case: {
+                        let name = match args.next() {
+                            Some(name) => {name},
+                            None => {break 'fn_case}
+                        };
+                        let remaining: Vec<&str> = args.collect();
+                        if let Err(e) = self.call_function(name, &remaining.join(" ")) {
+                            self.message = Some(e.to_string());
+                        }
+                    },
+                    "get" => 'get_case: {
+                        let key = args.next().filter(|key| !key.is_empty());
+                        if key.is_some_and(|key| self.frontend_settings.iter().any(|name| name == key)) {break 'get_case};
+                        let lines = config::get_fields(&self.config, key);
+                        match lines.as_slice() {
+                            [] => self.message = Some(format!("no setting '{}'", key.unwrap_or(""))),
+                            [line] => self.message = Some(line.clone()),
+                            _ => self.listing = Some(lines),
+                        }
+                    },
+                    // `set!` also writes the config file
+                    "set" | "set!" => 'set_case: {
+                        let persist = command.starts_with("set!");
+                        let key = match args.next() {
+                            Some(key) => {key},
+                            None => {break 'set_case}
+                        };
+                        if self.frontend_settings.iter().any(|name| name == key) {break 'set_case};
+                        let value: Vec<&str> = args.collect();
+                        let config = config::set_field(&self.config, key, value.join(" ").as_str());
+                        match config.and_then(|config| config.validate().map(|_| config)) {
+                            Ok(config) => {
+                                self.apply_config(config);
+                                // a workspace's precision changes only when it's set on purpose
+                                if key == "max_fractional_places" {self.max_fractional_places = self.config.max_fractional_places};
+                                self.message = config::get_fields(&self.config, Some(key)).pop();
+                                if persist {
+                                    match self.config.save() {
+                                        Ok(()) => {self.config_watch.forget_changes();},
+                                        Err(e) => self.message = Some(e),
+                                    }
+                                }
+                            },
+                            Err(e) => self.message = Some(e),
+                        }
+                    },
+                    "ws" | "workspace" => {
+                        let subcommand = args.next().unwrap_or("");
+                        let name = args.next().filter(|name| !name.is_empty());
+                        match (subcommand, name) {
+                            ("open", Some(name)) => self.open_workspace(name),
+                            ("new", Some(name)) => self.new_workspace(name),
+                            ("close", _) => self.close_workspace(),
+                            ("save", name) if name.is_some() || self.workspace.is_some() => self.save_workspace(name),
+                            ("save", None) => self.message = Some("no workspace open, use ':ws save <name>'".to_owned()),
+                            ("list", _) => {
+                                let names: Vec<String> = Workspace::list().into_iter()
+                                    .map(|name| if self.workspace.as_ref() == Some(&name) {format!("* {name}")} else {format!("  {name}")})
+                                    .collect();
+                                if names.is_empty() {
+                                    self.message = Some("no workspaces".to_owned());
+                                } else {
+                                    self.listing = Some(names);
+                                }
+                            },
+                            ("", _) => self.message = Some(match &self.workspace {
+                                Some(name) => format!("workspace '{name}'"),
+                                None => "no workspace open".to_owned(),
+                            }),
+                            _ => self.message = Some("usage: ws open|save|new|close|list [name]".to_owned()),
+                        }
+                    },
+                    "y" | "yank" => {
+                        match args.next().unwrap_or("") {
+                            "r" | "result" => self.copy_result(),
+                            _ => self.message = Some("usage: yank [result]".to_owned()),
+                        }
+                    },
+                    // `export csv|json|md [binary|decimal|hexadecimal] <path>`, values are in the current base without one
+                    "export" => 'export_case: {
+                        let format = match args.next().and_then(|format| ExportFormat::from_str(format, true).ok()) {
+                            Some(format) => {format},
+                            None => {
+                                self.message = Some("usage: export csv|json|md [base] <path>".to_owned());
+                                break 'export_case
+                            }
+                        };
+                        let mut remaining: Vec<&str> = args.collect();
+                        let base = remaining.first().and_then(|base| NumberBase::from_str(base, true).ok());
+                        if base.is_some() {remaining.remove(0);}
+                        let path = remaining.join(" ");
+                        if path.is_empty() {
+                            self.message = Some("usage: export csv|json|md [base] <path>".to_owned());
+                            break 'export_case
+                        }
+                        self.export(format, &base.unwrap_or(self.base.clone()), &path);
+                    },
+                    // `import [csv|list|env] <path>`, the format is guessed from the file without one
+                    "import" => 'import_case: {
+                        let mut remaining: Vec<&str> = args.collect();
+                        let format = remaining.first().and_then(|format| ImportFormat::from_str(format, true).ok());
+                        if format.is_some() {remaining.remove(0);}
+                        let path = remaining.join(" ");
+                        if path.is_empty() {
+                            self.message = Some("usage: import [csv|list|env] <path>".to_owned());
+                            break 'import_case
+                        }
+                        let text = match fs::read_to_string(&path) {
+                            Ok(text) => {text},
+                            Err(_) => {
+                                self.message = Some(format!("no file '{path}'"));
+                                break 'import_case
+                            }
+                        };
+                        let format = format.unwrap_or(ImportFormat::detect(&text));
+                        let reserved = |name: &str| Self::is_ans_register(name) || name == MEMORY;
+                        let imported = import::import(&text, format, &self.base, &self.variables, reserved);
+                        self.message = Some(imported.summary());
+                        if !imported.skipped.is_empty() {
+                            self.listing = Some(imported.skipped_lines());
+                        }
+                        self.variables.extend(imported.variables);
+                    },
                     "p" | "path" => {
                         let remaining: Vec<&str> = args.collect();
                         let path = remaining.join(" ");
                         self.vars_path = path;
                     },
                     "r" | "read" => 'r_case: {
+                        if !self.outside_workspace("r") {break 'r_case};
                         let remaining: Vec<&str> = args.collect();
                         let path: String = remaining.join(" ");
                         let path = Path::new(path.as_str());
-                        if !Path::exists(&path){break 'r_case};
+                        if !Path::exists(path){
+                            self.message = Some(format!("no file '{}'", path.to_string_lossy()));
+                            break 'r_case
+                        };
                         self.vars_path = path.to_string_lossy().into();
                         self.read_vars();
                     },
-                    "w" | "write" => {
+                    "w" | "write" => 'w_case: {
+                        if !self.outside_workspace("w") {break 'w_case};
                         let remaining: Vec<&str> = args.collect();
                         let path = remaining.join(" ");
                         self.vars_path = path;
@@ -228,3 +1151,264 @@ impl State {
         self.enter_equation_entry();
     } 
 }
+
+// `*` matches any run of characters and `?` any single one, a pattern without either matches as a substring
+pub fn matches_pattern(name: &str, pattern: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
+    }
+    fn matches(name: &[char], pattern: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|i| matches(&name[i..], rest)),
+            Some(('?', rest)) => !name.is_empty() && matches(&name[1..], rest),
+            Some((c, rest)) => name.first() == Some(c) && matches(&name[1..], rest),
+        }
+    }
+    matches(&name.chars().collect::<Vec<char>>(), &pattern.chars().collect::<Vec<char>>())
+}
+
+#[test]
+fn matches_pattern_test() {
+    assert!(matches_pattern("ans3", "ans"));
+    assert!(matches_pattern("ans3", "ans?"));
+    assert!(!matches_pattern("ans", "ans?"));
+    assert!(matches_pattern("rate_eur", "*eur"));
+    assert!(!matches_pattern("rate_usd", "*eur"));
+    assert!(matches_pattern("anything", ""));
+}
+
+#[cfg(test)]
+fn test_state() -> State {
+    State::with_config(Config::default())
+}
+
+#[cfg(test)]
+fn run(state: &mut State, command: &str) {
+    state.command = Some(command.to_owned());
+    state.execute_command();
+}
+
+#[test]
+fn mv_test() {
+    let mut state = test_state();
+    state.variables.insert("x".to_owned(), Num::from_integer(1.into()));
+    state.variables.insert("y".to_owned(), Num::from_integer(2.into()));
+    run(&mut state, "mv x y");
+    assert_eq!(state.message.take(), Some("'y' already exists, use mv! to replace it".to_owned()));
+    run(&mut state, "mv! x x");
+    assert_eq!(state.message.take(), None);
+    assert_eq!(state.variables.get("x"), Some(&Num::from_integer(1.into())));
+    run(&mut state, "mv! z y");
+    assert_eq!(state.message.take(), Some("no variable 'z'".to_owned()));
+    assert!(state.variables.contains_key("y"));
+    run(&mut state, "mv! x y");
+    assert_eq!(state.variables.get("y"), Some(&Num::from_integer(1.into())));
+    assert!(!state.variables.contains_key("x"));
+}
+
+#[test]
+fn memory_recall_undo_test() {
+    let mut state = test_state();
+    state.variables.insert(MEMORY.to_owned(), Num::from_integer(5.into()));
+    state.type_string("12".to_owned());
+    state.memory_recall();
+    assert_eq!(state.display(), "5");
+    state.undo();
+    assert_eq!(state.display(), "12");
+}
+
+#[test]
+fn memory_after_operator_test() {
+    // an operator without a right operand leaves the left one to add
+    let mut state = test_state();
+    state.type_string("5*".to_owned());
+    state.memory_add();
+    assert_eq!(state.memory(), Some(&Num::from_integer(5.into())));
+    state.type_string("3".to_owned());
+    state.memory_sub();
+    assert_eq!(state.memory(), Some(&Num::from_integer((-10).into())));
+    let mut state = test_state();
+    state.type_string("5/".to_owned());
+    state.memory_add();
+    assert_eq!((state.memory().cloned(), state.message.take()), (Some(Num::from_integer(5.into())), None));
+}
+
+#[test]
+fn type_string_test() {
+    let mut state = test_state();
+    state.type_string(":12".to_owned());
+    assert_eq!(state.command.take(), Some("12".to_owned()));
+    // backspace removes a whole character
+    state.type_string(":r /tmp/é".to_owned());
+    state.delete_one();
+    assert_eq!(state.command.take(), Some("r /tmp/".to_owned()));
+    assert_eq!(state.display(), "0");
+    state.type_string("1+2".to_owned());
+    state.undo();
+    assert_eq!(state.display(), "0");
+}
+
+#[test]
+fn calculations_test() {
+    // `*` after `10+5` evaluates it only with chaining on
+    let mut state = State::with_config(Config {chain_operations: true, ..Config::default()});
+    state.type_string("10+5*".to_owned());
+    state.type_string("2".to_owned());
+    state.evaluate();
+    let entries: Vec<(String, String)> = state.export_entries(&NumberBase::Hexadecimal).into_iter()
+        .filter(|entry| entry.kind == "calculation")
+        .map(|entry| (entry.expression, entry.value))
+        .collect();
+    assert_eq!(entries, vec![("0xA + 0x5".to_owned(), "0xF".to_owned()), ("0xF * 0x2".to_owned(), "0x1E".to_owned())]);
+    assert!(state.unsaved_calculations);
+}
+
+#[test]
+fn workspace_settings_test() {
+    let mut state = test_state();
+    state.global = Some(state.to_workspace());
+    state.apply_workspace(Workspace {max_fractional_places: 3, ..Default::default()});
+    state.workspace = Some("w".to_owned());
+    // reloading the config leaves the workspace's precision alone
+    let places = state.config.max_fractional_places + 1;
+    let config = config::set_field(&state.config, "max_fractional_places", &places.to_string()).unwrap();
+    state.apply_config(config);
+    assert_eq!((state.max_fractional_places, state.config.max_fractional_places), (3, places));
+    assert_eq!(state.to_workspace().max_fractional_places, 3);
+    // `ans` registers are only saved with `persist_ans`
+    state.variables.insert("ans".to_owned(), Num::from_integer(1.into()));
+    state.variables.insert("rate".to_owned(), Num::from_integer(2.into()));
+    assert_eq!(state.to_workspace().variables.keys().collect::<Vec<_>>(), vec!["rate"]);
+    state.config.persist_ans = true;
+    assert_eq!(state.to_workspace().variables.keys().collect::<Vec<_>>(), vec!["ans", "rate"]);
+    run(&mut state, "set max_fractional_places 4000000000");
+    assert_eq!(state.max_fractional_places, 3);
+    state.apply_workspace(Workspace {max_fractional_places: u32::MAX, ..Default::default()});
+    assert_eq!(state.max_fractional_places, config::MAX_FRACTIONAL_PLACES);
+    run(&mut state, "w");
+    assert_eq!(state.message.take(), Some("':w' doesn't work in workspace 'w', use ':ws close' first".to_owned()));
+    run(&mut state, "r /nonexistent");
+    assert_eq!(state.message.take(), Some("':r' doesn't work in workspace 'w', use ':ws close' first".to_owned()));
+}
+
+#[test]
+fn autosave_test() {
+    let mut state = State::with_config(Config {autosave: true, ..Config::default()});
+    let path = std::env::temp_dir().join("minicalc-autosave-test");
+    state.vars_path = path.to_string_lossy().into();
+    run(&mut state, "s l x");
+    state.autosave();
+    assert_eq!(fs::read_to_string(&path).ok().as_deref(), Some("x\n0\n"));
+    // nothing is written, or even serialized, without input
+    fs::remove_file(&path).unwrap();
+    state.variables.insert("y".to_owned(), Num::from_integer(1.into()));
+    state.autosave();
+    assert!(!path.exists());
+    state.type_string("2".to_owned());
+    state.autosave();
+    assert_eq!(fs::read_to_string(&path).ok().as_deref(), Some("x\n0\ny\n1\n"));
+    _ = fs::remove_file(&path);
+    _ = fs::remove_file(format!("{}.bak.1", path.display()));
+}
+
+#[test]
+fn vars_file_test() {
+    let mut state = test_state();
+    let path = std::env::temp_dir().join("minicalc-vars-file-test");
+    state.vars_path = path.to_string_lossy().into();
+    // names with brackets are still variables
+    state.variables.insert("f(x)".to_owned(), Num::from_integer(2.into()));
+    run(&mut state, "def double(x) = x * 2");
+    state.write_vars();
+    state.variables.clear();
+    state.functions.clear();
+    state.read_vars();
+    assert_eq!(state.message.take(), Some(format!("read vars '{}'", path.display())));
+    assert_eq!(state.variables.get("f(x)"), Some(&Num::from_integer(2.into())));
+    assert_eq!(state.functions.get("double").map(|function| function.signature("double")), Some("double(x)".to_owned()));
+    _ = fs::remove_file(&path);
+    _ = fs::remove_file(format!("{}.bak.1", path.display()));
+}
+
+#[test]
+fn workspace_undo_test() {
+    config::test_config_dir();
+    let mut state = test_state();
+    _ = fs::remove_file(Workspace::path("undo-test").unwrap());
+    state.type_string("12".to_owned());
+    run(&mut state, "ws new undo-test");
+    assert_eq!(state.workspace.as_deref(), Some("undo-test"));
+    // the equation from outside isn't an edit to undo in the workspace
+    state.undo();
+    assert_eq!(state.display(), "0");
+    run(&mut state, "ws close");
+    assert_eq!(state.display(), "12");
+    _ = fs::remove_file(Workspace::path("undo-test").unwrap());
+}
+
+#[test]
+fn read_config_test() {
+    config::test_config_dir();
+    let mut state = test_state();
+    fs::write(Config::path(), "(base: Hexadecimal, history_size: 10)").unwrap();
+    state.read_config();
+    assert_eq!(state.message.take(), Some("reloaded config".to_owned()));
+    assert_eq!((state.base.clone(), state.config.history_size), (NumberBase::Hexadecimal, 10));
+    // a malformed edit keeps the config from before
+    fs::write(Config::path(), "(base: Octal, history_size: 20)").unwrap();
+    state.read_config();
+    assert!(state.message.take().is_some_and(|message| message.starts_with("config not reloaded: ")));
+    assert_eq!((state.base.clone(), state.config.history_size), (NumberBase::Hexadecimal, 10));
+    fs::write(Config::path(), "(history_size: 4000000000)").unwrap();
+    state.read_config();
+    assert_eq!(state.config.history_size, 10);
+    _ = fs::remove_file(Config::path());
+}
+
+#[test]
+fn keymap_test() {
+    // a keymap with one entry keeps the other default bindings
+    let keymap = [("Ctrl+Y".to_owned(), Action::Undo)].into_iter().collect();
+    let mut state = State::with_config(Config {keymap, ..Config::default()});
+    state.type_string("1+2".to_owned());
+    let action = state.key_action(&Chord::new(Key::Enter, false, false, false));
+    assert_eq!(action, Some(Action::Evaluate));
+    state.perform(action.unwrap());
+    assert_eq!(state.display(), "3");
+}
+
+#[test]
+fn ans_test() {
+    let mut state = State::with_config(Config {ans_registers: 2, ..Config::default()});
+    for equation in ["1+1", "2+2", "3+3", "4+4"] {
+        state.type_string(equation.to_owned());
+        state.evaluate();
+        state.equation = Equation::default();
+    }
+    let ans = |state: &State, name: &str| state.variables.get(name).cloned();
+    let n = |i: i64| Some(Num::from_integer(i.into()));
+    assert_eq!((ans(&state, "ans"), ans(&state, "ans1"), ans(&state, "ans2")), (n(8), n(6), n(4)));
+    assert!(!state.variables.contains_key("ans3"));
+    // lowering `ans_registers` drops the registers past it
+    let config = config::set_field(&state.config, "ans_registers", "1").unwrap();
+    state.apply_config(config);
+    assert_eq!((ans(&state, "ans1"), ans(&state, "ans2")), (n(6), None));
+}
+
+#[test]
+fn base_test() {
+    let mut state = State::with_config(Config {base_cycle: vec![NumberBase::Hexadecimal, NumberBase::Binary], ..Config::default()});
+    // a base outside the cycle goes to its first one
+    state.cycle_base();
+    assert_eq!(state.base, NumberBase::Hexadecimal);
+    // the digits typed in hexadecimal are committed before the switch
+    state.type_string("1f".to_owned());
+    state.cycle_base();
+    assert_eq!((state.base.clone(), state.display()), (NumberBase::Binary, "0b11111".to_owned()));
+    // and the cycle wraps around
+    state.cycle_base();
+    assert_eq!(state.base, NumberBase::Hexadecimal);
+    state.set_base(NumberBase::Decimal);
+    assert_eq!(state.display(), "31");
+}
@@ -1,10 +1,11 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crossterm::terminal;
 use crossterm::cursor;
 use crossterm::queue;
 use crossterm::event;
 use crossterm::style::Print;
 use crate::minicalc::State;
+use crate::keymap::{Action, Chord, Key};
 use crate::Args;
 use std::io::Write;
 use std::io::stdout;
@@ -14,23 +15,34 @@ struct Cleanup;
 impl Drop for Cleanup {
     fn drop(&mut self) {
         _ = terminal::disable_raw_mode();
-        _ = queue!(stdout(), terminal::EnableLineWrap);
+        _ = queue!(stdout(), terminal::EnableLineWrap, event::DisableBracketedPaste);
         _ = stdout().flush();
     }
 }
 
 pub fn crossterm_main(args: Args) {
-    let mut state = State::default();
-    state.vars_path = args.vars;
+    let mut state = State {
+        vars_path: args.vars_path(),
+        terminal_clipboard: true,
+        ..Default::default()
+    };
     state.read_vars();
+    state.restore_session();
+    if let Some(name) = &args.workspace {
+        state.open_workspace(name);
+    }
+    for e in &state.config_errors {
+        eprintln!("{e}");
+    }
     
     if terminal::enable_raw_mode().is_err() {println!("terminal does not support raw mode, cant run term frontend"); return};
     
     _ = queue!(stdout(),
         terminal::DisableLineWrap,
+        event::EnableBracketedPaste,// pastes arrive as one event instead of a key per character
         cursor::SavePosition,
         cursor::EnableBlinking,
-        Print(state.display()),
+        Print(display_line(&mut state)),
     );
 
     _ = stdout().flush();
@@ -40,60 +52,142 @@ pub fn crossterm_main(args: Args) {
     loop {
         _ = queue!(stdout(),
             cursor::RestorePosition,
-            terminal::Clear {0: terminal::ClearType::CurrentLine}
+            terminal::Clear(terminal::ClearType::CurrentLine)
         );
         
-        if event::poll(Duration::from_millis(1000)).unwrap_or(false) {
-            match event::read().ok() {
-                Some(event) => {
-                    match event {
-                        event::Event::Key(event) => {
-                            match event.code {
-                                KeyCode::Char(char) => 'char_case: {
-                                    if char == ':' && state.command.is_none() {
-                                        state.enter_command_entry("".to_owned());
-                                        break 'char_case;
-                                    } 
-                                    state.type_string(char.to_string())
-                                },
-                                KeyCode::Backspace => {
-                                    state.delete_one();
-                                    state.cached_equation_display = None;
-                                },
-                                KeyCode::Enter => {
-                                    if state.command.is_some() {
-                                        execute_command(&mut state);
-                                        if state.exiting {return}
-                                    } else {
-                                        state.equation.eval_mut();
-                                        state.cached_equation_display = None;
-                                    }
-                                },
-                                KeyCode::Esc => {
-                                    if state.command.is_none() {
-                                        state.enter_equation_entry()
-                                    }
-                                },
-                                _ => {}
-                            }
-                        },
-                        event::Event::Paste(text) => {
-                            state.type_string(text)
-                        },
-                        _ => {}
-                    }
-                },
-                None => {}
+        // redraw sooner while a number is formatted in the background
+        let timeout = if state.format_cache.is_pending() {50} else {1000};
+        state.poll_formats();
+        if event::poll(Duration::from_millis(timeout)).unwrap_or(false) {
+            if let Ok(event) = event::read() {
+                state.message = None;
+                match event {
+                    event::Event::Key(event) if event.kind != KeyEventKind::Release => 'key_case: {
+                        let chord = match chord(&event) {
+                            Some(chord) => chord,
+                            None => break 'key_case,
+                        };
+                        match state.key_action(&chord) {
+                            Some(Action::Evaluate) if state.command.is_some() => {
+                                execute_command(&mut state);
+                                if state.exiting {
+                                    state.before_exit();
+                                    return
+                                }
+                            },
+                            Some(action) => {state.perform(action);},
+                            None if chord.ctrl || chord.alt => {},
+                            None => match chord.key {
+                                Key::Char(char) => state.type_string(char.to_string()),
+                                Key::Space => state.type_string(" ".to_owned()),
+                                _ => {},
+                            },
+                        }
+                    },
+                    event::Event::Paste(text) => {
+                        state.type_string(text)
+                    },
+                    _ => {}
+                }
+            }
+        }
+        state.reload_config();
+        state.autosave();
+        if let Some(text) = state.terminal_copy.take() {
+            _ = queue!(stdout(), Print(osc52(&text)));
+        }
+        // listings go on their own lines above the prompt, which moves down
+        if let Some(listing) = state.listing.take() {
+            for line in listing {
+                _ = queue!(stdout(), Print(line), Print("\r\n"));
             }
+            _ = queue!(stdout(), cursor::SavePosition);
         }
         _ = queue!(stdout(),
-            Print(state.display())
+            Print(display_line(&mut state))
         );
+        let after_cursor = chars_after_cursor(&state);
+        if after_cursor > 0 {
+            _ = queue!(stdout(), cursor::MoveLeft(after_cursor as u16));
+        }
 
         _ = stdout().flush();
     }
 }
 
+// the display with the base and memory indicators in front of it and the last message after it
+fn display_line(state: &mut State) -> String {
+    let mut line = format!("[{}] ", state.base.badge());
+    if let Some(memory) = state.memory().cloned() {
+        line += format!("[M {}] ", state.format(&memory)).as_str();
+    }
+    line += state.display().as_str();
+    if let Some(message) = &state.message {
+        line += format!("  ({message})").as_str();
+    }
+    line
+}
+
+// how far the terminal cursor is from the end of `display_line`
+fn chars_after_cursor(state: &State) -> usize {
+    let message = match &state.message {
+        Some(message) => format!("  ({message})").chars().count(),
+        None => 0,
+    };
+    if state.command.is_some() {message} else {state.equation.chars_after_cursor() + message}
+}
+
+fn chord(event: &KeyEvent) -> Option<Chord> {
+    let key = match event.code {
+        KeyCode::Char(' ') => Key::Space,
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        _ => return None,
+    };
+    let modifiers = event.modifiers;
+    Some(Chord::new(key, modifiers.contains(KeyModifiers::CONTROL), modifiers.contains(KeyModifiers::ALT), modifiers.contains(KeyModifiers::SHIFT)))
+}
+
+// asks the terminal to put `text` on the clipboard, which works over ssh
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn base64_test() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64("1 + 0xFF".as_bytes()), "MSArIDB4RkY=");
+}
+
 fn execute_command(state: &mut State) {
     // extra logic
     state.execute_command()
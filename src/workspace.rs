@@ -0,0 +1,275 @@
+use serde::{Serialize, Deserialize};
+use ron::ser::{to_string_pretty, PrettyConfig};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use crate::config::{config_dir, read_file, write_atomic, Config};
+use crate::math::{Num, base::NumberBase, equation::{Equation, DigitBuffer}, expr::Function, operation::Operation, parsefmt};
+
+// an equation as it is being typed, including digits like the trailing zeros of `1.50`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct SavedEquation {
+    pub left: String,// exact values like `1/3`
+    pub operation: Option<Operation>,
+    pub right: Option<String>,
+    pub right_entered: bool,
+    pub left_digits: Option<DigitBuffer>,
+    pub right_digits: Option<DigitBuffer>,
+    pub right_percent: bool,
+    pub repeat: Option<(Operation, String, bool)>,// the operation Enter repeats, with its exact right operand and percent flag
+    pub left_var: Option<String>,
+    pub right_var: Option<String>,
+}
+
+impl Default for SavedEquation {
+    fn default() -> Self {
+        Self::new(&Equation::default())
+    }
+}
+
+impl SavedEquation {
+    pub fn new(equation: &Equation) -> Self {
+        let equation = equation.clone();
+        Self {
+            left: equation.left.to_string(),
+            operation: equation.operation,
+            right: equation.right.map(|n| n.to_string()),
+            right_entered: equation.right_entered,
+            // digits that are still the exact value they were formatted from are just that value
+            left_digits: equation.left_digits.filter(|buffer| buffer.exact.is_none()),
+            right_digits: equation.right_digits.filter(|buffer| buffer.exact.is_none()),
+            right_percent: equation.right_percent,
+            repeat: equation.repeat.map(|(operation, right, percent)| (operation, right.to_string(), percent)),
+            left_var: equation.left_var,
+            right_var: equation.right_var,
+        }
+    }
+
+    // None if a value doesn't parse
+    pub fn equation(&self) -> Option<Equation> {
+        let right = match &self.right {
+            Some(right) => Some(parsefmt::parse(right.clone())?),
+            None => None,
+        };
+        let repeat = match &self.repeat {
+            Some((operation, right, percent)) => Some((*operation, parsefmt::parse(right.clone())?, *percent)),
+            None => None,
+        };
+        // an operation needs a right operand and the other way around
+        if self.operation.is_some() != right.is_some() {return None};
+        // digits that aren't digits of their base, or a cursor outside of them, would panic or be lost when typing
+        let valid = |buffer: &DigitBuffer| buffer.text.is_char_boundary(buffer.cursor) && parsefmt::parse_digits(&buffer.text, buffer.base.clone()).is_some();
+        Some(Equation {
+            left: parsefmt::parse(self.left.clone())?,
+            operation: self.operation,
+            right,
+            right_entered: self.right_entered,
+            left_digits: self.left_digits.clone().filter(valid),
+            right_digits: self.right_digits.clone().filter(valid),
+            right_percent: self.right_percent,
+            repeat,
+            left_var: self.left_var.clone(),
+            right_var: self.right_var.clone(),
+        })
+    }
+}
+
+// a project's variables, functions, history, equation and settings, saved as `workspaces/<name>.ron`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Workspace {
+    pub variables: BTreeMap<String, String>,// exact values like `1/3`
+    pub functions: BTreeMap<String, String>,// signatures like `mv(x)` to bodies
+    pub history: Vec<String>,
+    pub calculations: Vec<(SavedEquation, String)>,// evaluated equations and their exact results
+    pub equation: SavedEquation,
+    pub base: NumberBase,
+    pub percent_mode: bool,
+    pub max_fractional_places: u32,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl Workspace {
+    // an empty workspace with the settings from `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            variables: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            history: vec![],
+            calculations: vec![],
+            equation: SavedEquation::default(),
+            base: config.base.clone(),
+            percent_mode: config.percent_mode,
+            max_fractional_places: config.max_fractional_places,
+        }
+    }
+
+    pub fn dir() -> PathBuf {
+        config_dir().join("workspaces")
+    }
+
+    // names are plain file names, so a workspace can't be saved outside `dir`
+    pub fn path(name: &str) -> Result<PathBuf, String> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(format!("bad workspace name '{name}'"));
+        }
+        Ok(Self::dir().join(format!("{name}.ron")))
+    }
+
+    pub fn exists(name: &str) -> bool {
+        Self::path(name).is_ok_and(|path| path.exists())
+    }
+
+    pub fn open(name: &str) -> Result<Self, String> {
+        match read_file::<Self>(&Self::path(name)?) {
+            Ok(Some((workspace, _))) => Ok(workspace),
+            Ok(None) => Err(format!("no workspace '{name}'")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        let path = Self::path(name)?;
+        _ = fs::create_dir_all(Self::dir());
+        let data = to_string_pretty(self, PrettyConfig::default()).map_err(|e| e.to_string())?;
+        write_atomic(&path, &data).map_err(|_| format!("failed writing workspace '{}'", path.display()))
+    }
+
+    // the names of every saved workspace, sorted
+    pub fn list() -> Vec<String> {
+        let entries = match fs::read_dir(Self::dir()) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut names: Vec<String> = entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "ron" {return None};
+            Some(path.file_stem()?.to_string_lossy().to_string())
+        }).collect();
+        names.sort();
+        names
+    }
+
+    // the variables parsed, skipping any that don't
+    pub fn parsed_variables(&self) -> impl Iterator<Item = (String, Num)> + '_ {
+        self.variables.iter().filter_map(|(name, n)| Some((name.clone(), parsefmt::parse(n.clone())?)))
+    }
+
+    // the functions parsed, skipping any that don't
+    pub fn parsed_functions(&self) -> impl Iterator<Item = (String, Function)> + '_ {
+        self.functions.iter().filter_map(|(signature, body)| Function::parse_definition(format!("{signature} = {body}").as_str()).ok())
+    }
+
+    // the calculations parsed, skipping any that don't
+    pub fn parsed_calculations(&self) -> impl Iterator<Item = (Equation, Num)> + '_ {
+        Self::parse_calculations(&self.calculations)
+    }
+
+    pub fn saved_calculations(calculations: &[(Equation, Num)]) -> Vec<(SavedEquation, String)> {
+        calculations.iter().map(|(equation, n)| (SavedEquation::new(equation), n.to_string())).collect()
+    }
+
+    fn parse_calculations(calculations: &[(SavedEquation, String)]) -> impl Iterator<Item = (Equation, Num)> + '_ {
+        calculations.iter().filter_map(|(equation, n)| Some((equation.equation()?, parsefmt::parse(n.clone())?)))
+    }
+
+    // the calculations made outside workspaces, kept for `:export` and `--export`
+    pub fn calculations_path() -> PathBuf {
+        config_dir().join("calculations")
+    }
+
+    pub fn load_calculations() -> Vec<(Equation, Num)> {
+        match read_file::<Vec<(SavedEquation, String)>>(&Self::calculations_path()) {
+            Ok(Some((calculations, _))) => Self::parse_calculations(&calculations).collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn save_calculations(calculations: &[(Equation, Num)]) -> std::io::Result<()> {
+        write_atomic(&Self::calculations_path(), &ron::to_string(&Self::saved_calculations(calculations)).unwrap_or_default())
+    }
+
+    pub fn saved_variables(variables: &HashMap<String, Num>) -> BTreeMap<String, String> {
+        variables.iter().map(|(name, n)| (name.clone(), n.to_string())).collect()
+    }
+
+    pub fn saved_functions(functions: &HashMap<String, Function>) -> BTreeMap<String, String> {
+        functions.iter().map(|(name, function)| (function.signature(name), function.source.clone())).collect()
+    }
+}
+
+// the equation and mode when minicalc was closed, restored on launch with the `restore_session` config
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Session {
+    pub equation: SavedEquation,
+    pub base: NumberBase,
+    pub percent_mode: bool,
+    pub workspace: Option<String>,// the workspace that was open
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        let config = Config::default();
+        Self {equation: SavedEquation::default(), base: config.base, percent_mode: config.percent_mode, workspace: None}
+    }
+}
+
+impl Session {
+    pub fn path() -> PathBuf {
+        config_dir().join("session")
+    }
+
+    // None without a session file, or with one that doesn't parse
+    pub fn load() -> Option<Self> {
+        read_file::<Self>(&Self::path()).ok()?.map(|(session, _)| session)
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write_atomic(&Self::path(), &to_string_pretty(self, PrettyConfig::default()).unwrap_or_default())
+    }
+}
+
+#[test]
+fn workspace_test() {
+    use crate::math::NumComponent;
+    let equation = Equation {
+        left: Num::new(NumComponent::from(1), NumComponent::from(3)),
+        operation: Some(Operation::Mul),
+        right: Some(Num::from(NumComponent::from(0))),
+        right_var: Some("rate".to_owned()),
+        ..Default::default()
+    };
+    let saved = SavedEquation::new(&equation);
+    assert_eq!(saved.left, "1/3");
+    assert_eq!(saved.equation(), Some(equation));
+    // Enter repeats the last operation after a restore
+    let repeating = Equation {repeat: Some((Operation::Mul, Num::new(NumComponent::from(3), NumComponent::from(2)), true)), ..Default::default()};
+    let data = to_string_pretty(&SavedEquation::new(&repeating), PrettyConfig::default()).unwrap();
+    assert_eq!(ron::from_str::<SavedEquation>(&data).ok().and_then(|saved| saved.equation()), Some(repeating));
+    // the digits being typed are kept as typed
+    let equation = Equation {left_digits: Some(DigitBuffer {text: "1.50".to_owned(), cursor: 4, base: NumberBase::Decimal, exact: None}), ..Default::default()};
+    assert_eq!(SavedEquation::new(&equation).equation(), Some(equation.clone()));
+    let bad = |text: &str, cursor| SavedEquation {left_digits: Some(DigitBuffer {text: text.to_owned(), cursor, base: NumberBase::Binary, exact: None}), ..Default::default()};
+    assert_eq!(bad("102", 1).equation().map(|equation| equation.left_digits), Some(None));
+    assert_eq!(bad("1é", 2).equation().map(|equation| equation.left_digits), Some(None));
+    assert_eq!(bad("10", 3).equation().map(|equation| equation.left_digits), Some(None));
+
+    let calculations = Workspace::saved_calculations(&[(equation.clone(), Num::from(NumComponent::from(2)))]);
+    let workspace = Workspace {equation: saved, history: vec!["x".to_owned()], calculations, ..Default::default()};
+    assert_eq!(workspace.parsed_calculations().collect::<Vec<_>>(), vec![(equation, Num::from(NumComponent::from(2)))]);
+    let data = to_string_pretty(&workspace, PrettyConfig::default()).unwrap();
+    assert_eq!(ron::from_str::<Workspace>(&data), Ok(workspace));
+    // missing fields fall back to the defaults
+    assert_eq!(ron::from_str::<Workspace>("(base: Hexadecimal)").map(|w| w.base), Ok(NumberBase::Hexadecimal));
+
+    crate::config::test_config_dir();
+    assert!(Workspace::path("../evil").is_err());
+    assert!(Workspace::path("firmware").is_ok());
+}